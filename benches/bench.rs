@@ -7,21 +7,26 @@ extern crate auto_correct;
 use criterion::Criterion;
 use auto_correct::prelude::*;
 
-fn bench_base(c: &mut Criterion) {
+fn service_with_mode(mode: RunMode) -> AutoCorrect {
     let mut service = AutoCorrect::new();
     service.set_max_edit(2);
+    service.set_run_mode(mode);
+    service
+}
+
+fn bench_base(c: &mut Criterion) {
+    let service = service_with_mode(RunMode::SpaceSensitive);
 
     c.bench_function("auto_correct: 'tets'", move |b| {
         b.iter(|| {
             let results = service.candidates(String::from("tets"));
-            assert_eq!(results.len(), 367usize);
+            assert_eq!(results.len(), 372usize);
         })
     });
 }
 
 fn bench_long(c: &mut Criterion) {
-    let mut service = AutoCorrect::new();
-    service.set_max_edit(2);
+    let service = service_with_mode(RunMode::SpaceSensitive);
 
     c.bench_function("auto_correct: 'wahtabout'", move |b| {
         b.iter(|| {
@@ -31,5 +36,90 @@ fn bench_long(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, bench_base); //, bench_long);
-criterion_main!(benches);
\ No newline at end of file
+fn bench_fan_out(c: &mut Criterion) {
+    let service = service_with_mode(RunMode::SpaceSensitive);
+
+    // the search the trie walk replaced, which misses 12 of the words the walk finds, where a
+    // transpose and another edit overlap
+    c.bench_function("auto_correct (fan out): 'tets'", move |b| {
+        b.iter(|| {
+            let results = service.candidates_fan_out(String::from("tets"));
+            assert_eq!(results.len(), 360usize);
+        })
+    });
+}
+
+fn bench_speed_mode(c: &mut Criterion) {
+    let service = service_with_mode(RunMode::SpeedSensitive);
+
+    c.bench_function("auto_correct (speed sensitive): 'tets'", move |b| {
+        b.iter(|| service.candidates(String::from("tets")))
+    });
+}
+
+fn bench_symmetric_delete(c: &mut Criterion) {
+    let service = service_with_mode(RunMode::SymmetricDelete);
+
+    c.bench_function("auto_correct (symmetric delete): 'tets'", move |b| {
+        b.iter(|| service.candidates(String::from("tets")))
+    });
+}
+
+fn bench_automaton(c: &mut Criterion) {
+    let service = service_with_mode(RunMode::Automaton);
+
+    c.bench_function("auto_correct (automaton): 'tets'", move |b| {
+        b.iter(|| {
+            let results = service.candidates(String::from("tets"));
+            assert_eq!(results.len(), 372usize);
+        })
+    });
+}
+
+fn bench_bktree(c: &mut Criterion) {
+    let service = service_with_mode(RunMode::BkTree);
+
+    c.bench_function("auto_correct (bk-tree): 'tets'", move |b| {
+        b.iter(|| service.candidates(String::from("tets")))
+    });
+}
+
+fn bench_top_k(c: &mut Criterion) {
+    let service = service_with_mode(RunMode::SpaceSensitive);
+
+    c.bench_function("auto_correct (top 3): 'tets'", move |b| {
+        b.iter(|| {
+            let results = service.candidates_top_k(String::from("tets"), 3);
+            assert_eq!(results.len(), 3usize);
+        })
+    });
+}
+
+fn bench_top_k_deep(c: &mut Criterion) {
+    let mut service = service_with_mode(RunMode::SpaceSensitive);
+    service.set_max_edit(3);
+
+    c.bench_function("auto_correct (top 3, max edit 3): 'speling'", move |b| {
+        b.iter(|| service.candidates_top_k(String::from("speling"), 3))
+    });
+}
+
+// each fanned out query takes seconds, so fewer samples
+criterion_group!(
+    name = fan_out;
+    config = Criterion::default().sample_size(10);
+    targets = bench_fan_out
+);
+
+criterion_group!(
+    benches,
+    bench_base,
+    bench_long,
+    bench_speed_mode,
+    bench_symmetric_delete,
+    bench_automaton,
+    bench_bktree,
+    bench_top_k,
+    bench_top_k_deep
+);
+criterion_main!(benches, fan_out);
@@ -1,123 +1,216 @@
 #![allow(dead_code)]
 
+use std::collections::HashSet;
 use std::env;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{BufRead, BufReader, Write};
-use std::path::{Path, PathBuf};
-use std::sync::mpsc;
+use std::path::Path;
+use std::sync::{mpsc, Arc};
 use std::thread;
 
-const ALPHABET_EN: &'static str = "abcdefghijklmnopqrstuvwxyz";
+// Keep them in sync with the alphabets of the locales in `support`.
+const ALPHABET_EN: &str = "abcdefghijklmnopqrstuvwxyz'-àâçéèêëîïñôöüû";
+const ALPHABET_DE: &str = "abcdefghijklmnopqrstuvwxyzäöüß-";
+const ALPHABET_FR: &str = "abcdefghijklmnopqrstuvwxyz'-àâæçéèêëîïôœùûüÿ";
+const ALPHABET_ES: &str = "abcdefghijklmnopqrstuvwxyzáéíñóúü-";
+const ALPHABET_IT: &str = "abcdefghijklmnopqrstuvwxyz'-àèéìíîòóùú";
+
+// The header of the neighbor index, the runtime refuses to load an index of another version.
+// Keep it in sync with `hybrid::NEIGHBORS_HEADER`.
+const NEIGHBORS_HEADER: &str = "#auto_correct:neighbors:v1";
+const NEIGHBORS_FILE: &str = "freq_50k_preproc.txt";
+const WORKERS: usize = 4;
 
 fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=SKIP_DICT_REBUILD");
+    println!("cargo:rerun-if-env-changed=LOCALE");
+    println!("cargo:rerun-if-env-changed=OVERRIDE_DICT");
+
     if let Ok(s) = env::var("SKIP_DICT_REBUILD") {
-        if s != String::from("false") {
+        if s != "false" {
             return;
         }
 
-        let out_dir =
-            if let Ok(result) = env::var("LOCALE") {
-                format!("./resources/{}/", result.to_lowercase())
-            } else {
-                format!("./resources/{}/", String::from("en-us"))
-            };
+        let locale = env::var("LOCALE")
+            .map(|result| result.to_lowercase())
+            .unwrap_or_else(|_| String::from("en-us"));
 
-        let dest_path = Path::new(&out_dir).join("freq_50k_precalc.txt");
-        let mut f = File::create(&dest_path).unwrap();
-
-        refresh_dict(&out_dir, &mut f);
+        refresh_dict(&format!("./resources/{}/", locale), alphabet(&locale));
     }
 }
 
-fn find_variations(word: String) -> Vec<String> {
-    let mut result: Vec<String> = Vec::new();
-    let mut base: String;
+fn alphabet(locale: &str) -> &'static str {
+    match locale {
+        "de" => ALPHABET_DE,
+        "fr" => ALPHABET_FR,
+        "es" => ALPHABET_ES,
+        "it" => ALPHABET_IT,
+        _ => ALPHABET_EN,
+    }
+}
 
-    let mut replace: String;
-    let mut removed: char;
+fn find_variations(word: &str, alphabet: &str) -> Vec<String> {
+    let runes: Vec<char> = word.chars().collect();
+    let len = runes.len();
+    let mut result: Vec<String> = Vec::with_capacity(54 * len + 26);
 
-    let len = word.len() + 1;
-    for pos in 0..len {
-        if pos < len - 1 {
-            base = word.clone();
+    let build = |left: &[char], mid: &[char], right: &[char]| -> String {
+        left.iter().chain(mid.iter()).chain(right.iter()).collect()
+    };
 
+    for pos in 0..=len {
+        if pos < len {
             // deletes
-            removed = base.remove(pos);
-            result.push(base.clone());
+            result.push(build(&runes[..pos], &[], &runes[pos + 1..]));
 
-            // replaces
-            for chara in ALPHABET_EN.chars() {
-                if chara == removed {
-                    continue;
-                }
-
-                replace = base.clone();
-                replace.insert(pos, chara);
-
-                result.push(replace.clone());
+            // transposes
+            if pos + 1 < len {
+                result.push(build(&runes[..pos], &[runes[pos + 1], runes[pos]], &runes[pos + 2..]));
             }
 
-            // transposes
-            if pos > 0 {
-                base.insert(pos - 1, removed);
-                result.push(base.clone());
+            // replaces
+            for chara in alphabet.chars() {
+                if chara != runes[pos] {
+                    result.push(build(&runes[..pos], &[chara], &runes[pos + 1..]));
+                }
             }
         }
 
         // inserts
-        for chara in ALPHABET_EN.chars() {
-            base = word.clone();
-            base.insert(pos, chara);
-
-            result.push(base.clone());
+        for chara in alphabet.chars() {
+            result.push(build(&runes[..pos], &[chara], &runes[pos..]));
         }
     }
 
     result
 }
 
-fn refresh_dict(source_dir: &String, target: &mut File) {
-    let (tx, rx) = mpsc::channel();
-    let source = source_dir.clone();
+fn load_words(path: &Path) -> Option<Vec<String>> {
+    if !path.exists() || !path.is_file() {
+        return None;
+    }
+
+    let file = File::open(path).ok()?;
+    let reader = BufReader::new(file);
+    let mut words = Vec::new();
+    let mut seen = HashSet::new();
 
-    thread::spawn(move || {
-        let path =
-            if let Ok(override_dict) = env::var("OVERRIDE_DICT") {
-                PathBuf::from(&override_dict)
-            } else {
-                Path::new(&source).join("freq_50k.txt")
-            };
+    for line in reader.lines().map_while(Result::ok) {
+        let temp: Vec<&str> = line.splitn(2, ',').collect();
+        if temp[0].is_empty() {
+            continue;
+        }
 
-        if !path.exists() || !path.is_file() {
+        // the runtime looks the words up in lower case, as the trie keeps them
+        let word = temp[0].to_lowercase();
+        if seen.insert(word.to_owned()) {
+            words.push(word);
+        }
+    }
+
+    Some(words)
+}
+
+fn refresh_dict(source_dir: &str, alphabet: &'static str) {
+    // the index of an override dictionary is computed from its words when the service loads
+    // it, and writing one here would replace the locale's bundled index with the neighbors of
+    // another word list
+    if let Ok(override_dict) = env::var("OVERRIDE_DICT") {
+        println!(
+            "cargo:warning=Skipping the neighbor index for the override dictionary {}, the service computes it when loading the dictionary",
+            override_dict
+        );
+        return;
+    }
+
+    let path = Path::new(source_dir).join("freq_50k.txt");
+
+    println!("cargo:rerun-if-changed={}", path.display());
+
+    let words = match load_words(&path) {
+        Some(words) => Arc::new(words),
+        None => {
             eprintln!("Unable to open the source dictionary from path: {:?}...", path);
             return;
-        }
+        },
+    };
+
+    let known: Arc<HashSet<String>> =
+        Arc::new(words.iter().cloned().collect());
+
+    // split the words among the workers, each reports back the lines of its own chunk
+    let (tx, rx) = mpsc::channel();
+    let chunk = words.len() / WORKERS + 1;
 
-        let file = File::open(path).expect("file not found");
-        let reader = BufReader::new(file);
+    for id in 0..WORKERS {
+        let (words, known, tx) = (Arc::clone(&words), Arc::clone(&known), tx.clone());
 
-        for raw_line in reader.lines() {
-            if let Ok(line) = raw_line {
-                tx.send(line).unwrap();
+        thread::spawn(move || {
+            let start = (id * chunk).min(words.len());
+            let end = ((id + 1) * chunk).min(words.len());
+            let mut lines = Vec::with_capacity(end - start);
+
+            for word in &words[start..end] {
+                lines.push(neighbors_line(word, &known, alphabet));
             }
+
+            tx.send((id, lines)).unwrap();
+        });
+    }
+
+    drop(tx);
+
+    let mut chunks: Vec<(usize, Vec<Option<String>>)> = rx.iter().collect();
+    chunks.sort_by_key(|(id, _)| *id);
+
+    let mut result = String::with_capacity(2 * 1024 * 1024);
+    result.push_str(NEIGHBORS_HEADER);
+    result.push('\n');
+
+    for (_, lines) in chunks {
+        for line in lines.into_iter().flatten() {
+            result.push_str(&line);
+            result.push('\n');
         }
+    }
+
+    // write to a temp file first, so a failed build never leaves a truncated index behind
+    let dest_path = Path::new(source_dir).join(NEIGHBORS_FILE);
+    let temp_path = Path::new(source_dir).join(format!("{}.tmp", NEIGHBORS_FILE));
+
+    let mut f = File::create(&temp_path)
+        .unwrap_or_else(|err| panic!("Unable to create {}: {}", temp_path.display(), err));
+    f.write_all(result.as_bytes())
+        .unwrap_or_else(|err| panic!("Unable to write {}: {}", temp_path.display(), err));
+    fs::rename(&temp_path, &dest_path).unwrap_or_else(|err| {
+        panic!("Unable to move {} to {}: {}", temp_path.display(), dest_path.display(), err)
     });
+}
 
-    let mut result: String;
+/// Format the word's line in the neighbor index as `word^n1;n2;...;`, or `None` if the word has
+/// no known neighbors within 1 edit distance.
+fn neighbors_line(word: &str, known: &HashSet<String>, alphabet: &str) -> Option<String> {
+    let mut neighbors: Vec<String> = Vec::new();
 
-    for entry in rx {
-        let temp: Vec<&str> = entry.splitn(2, ",").collect();
-        if temp[0].is_empty() {
-            continue;
+    for variation in find_variations(word, alphabet) {
+        if variation != word && known.contains(&variation) && !neighbors.contains(&variation) {
+            neighbors.push(variation);
         }
+    }
 
-        result = temp[0].to_owned();
-
-        //TODO: call `find_variations` to get all words within 1 edit distance, then save it to the
-        //      designated location.
+    if neighbors.is_empty() {
+        return None;
+    }
 
-        result.push_str("\r\n");
+    let mut line = String::with_capacity(word.len() * (neighbors.len() + 1) * 2);
+    line.push_str(word);
+    line.push('^');
 
-        target.write(result.as_bytes()).unwrap();
+    for neighbor in neighbors {
+        line.push_str(&neighbor);
+        line.push(';');
     }
-}
\ No newline at end of file
+
+    Some(line)
+}
@@ -7,8 +7,8 @@ use std::time::{SystemTime};
 use auto_correct::prelude::*;
 use hashbrown::HashSet;
 
-static OPT: &'static str = "OPT";
-static EXIT: &'static str = "EXIT";
+static OPT: &str = "OPT";
+static EXIT: &str = "EXIT";
 static LEN: u32 = 10;
 
 fn main() {
@@ -57,9 +57,9 @@ fn main() {
                 let e = now.elapsed().unwrap();
 
                 println!("\nResults:\n");
-                for idx in 0..results.len() {
+                for (idx, result) in results.iter().enumerate() {
                     println!("Suggestion #{}: {}; Score: {}; Edit Distance: {}",
-                             idx, results[idx].word, results[idx].score, results[idx].edit);
+                             idx, result.word, result.score, result.edit);
                 }
 
                 println!("\nTime elapsed: {:?}", e.div(LEN));
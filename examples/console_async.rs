@@ -7,8 +7,8 @@ use std::time::{SystemTime};
 
 use auto_correct::prelude::*;
 
-static OPT: &'static str = "OPT";
-static EXIT: &'static str = "EXIT";
+static OPT: &str = "OPT";
+static EXIT: &str = "EXIT";
 
 fn main() {
     let mut correct_service = AutoCorrect::new();
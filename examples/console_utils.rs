@@ -4,6 +4,8 @@ use auto_correct::prelude::*;
 use auto_correct::ServiceUtils;
 
 fn main() {
-    let correct_service = AutoCorrect::new();
-    correct_service.refresh_hybrid_dict(None);
+    let mut correct_service = AutoCorrect::new();
+    if let Err(e) = correct_service.refresh_hybrid_dict(None) {
+        eprintln!("Failed to refresh the dictionary: {}", e);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,201 @@
+use crossbeam_channel as channel;
+use hashbrown::HashMap;
+
+use candidate::Candidate;
+use dynamic::Context;
+
+// the placeholder of the runes before the start of the word
+static NO_SYMBOL: usize = usize::MAX;
+
+/// The Damerau-Levenshtein automaton of a query word for a fixed `max_edit`: a DFA whose states
+/// are the DP rows of the prefixes read so far against the word, with every cell capped at
+/// `max_edit + 1`. A row only depends on the input positions each rune matches, so the alphabet
+/// shrinks to the distinct runes of the word, plus one symbol for all the other runes, and the
+/// DFA is finite.
+///
+/// A transpose reaches back to the last rune matching the current input, with the runes in
+/// between inserted, so it's only within `max_edit` if it reaches back no further than `max_edit`
+/// runes. Each state hence keeps the rows and the symbols of that window, besides the current row.
+///
+/// The states are compiled on demand, the first time a transition reaches them, so a query only
+/// pays for the part of the DFA the dictionary actually walks through.
+pub(crate) struct Automaton {
+    input: Vec<usize>,
+    symbols: Vec<char>,
+    cap: usize,
+    states: Vec<State>,
+    ids: HashMap<State, usize>,
+    transitions: Vec<Vec<Target>>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct State {
+    rows: Vec<Vec<u8>>,
+    runes: Vec<usize>,
+}
+
+#[derive(Clone, Copy)]
+enum Target {
+    Pending,
+    Dead,
+    State(usize),
+}
+
+impl Automaton {
+    pub(crate) fn new(word: &str, max_edit: u8) -> Self {
+        let mut symbols: Vec<char> = Vec::new();
+        for rune in word.chars() {
+            if !symbols.contains(&rune) {
+                symbols.push(rune);
+            }
+        }
+
+        let input: Vec<usize> = word
+            .chars()
+            .map(|rune| symbols.iter().position(|symbol| *symbol == rune).unwrap_or(NO_SYMBOL))
+            .collect();
+
+        let cap = max_edit as usize + 1;
+        let window = max_edit as usize;
+
+        let mut rows = vec![vec![cap as u8; input.len() + 1]; window];
+        rows.push((0..=input.len()).map(|j| j.min(cap) as u8).collect());
+
+        let mut automaton = Automaton {
+            input,
+            symbols,
+            cap,
+            states: Vec::new(),
+            ids: HashMap::new(),
+            transitions: Vec::new(),
+        };
+
+        automaton.add_state(State { rows, runes: vec![NO_SYMBOL; window] });
+        automaton
+    }
+
+    pub(crate) fn start(&self) -> usize {
+        0
+    }
+
+    /// The state after reading the `rune` from the `state`, or `None` if no word with the prefix
+    /// read so far is within `max_edit`.
+    pub(crate) fn step(&mut self, state: usize, rune: char) -> Option<usize> {
+        let symbol = self.symbols
+            .iter()
+            .position(|symbol| *symbol == rune)
+            .unwrap_or(self.symbols.len());
+
+        let target = match self.transitions[state][symbol] {
+            Target::Pending => {
+                let target = match self.next_state(state, symbol) {
+                    Some(next) => match self.ids.get(&next) {
+                        Some(id) => Target::State(*id),
+                        None => Target::State(self.add_state(next)),
+                    },
+                    None => Target::Dead,
+                };
+
+                self.transitions[state][symbol] = target;
+                target
+            },
+            target => target,
+        };
+
+        match target {
+            Target::State(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// The edit distance of the prefix read so far, if the `state` is accepting.
+    pub(crate) fn distance(&self, state: usize) -> Option<u8> {
+        let rows = &self.states[state].rows;
+        let distance = rows[rows.len() - 1][self.input.len()];
+
+        if (distance as usize) < self.cap {
+            Some(distance)
+        } else {
+            None
+        }
+    }
+
+    fn add_state(&mut self, state: State) -> usize {
+        let id = self.states.len();
+
+        self.ids.insert(state.clone(), id);
+        self.states.push(state);
+        self.transitions.push(vec![Target::Pending; self.symbols.len() + 1]);
+
+        id
+    }
+
+    /// The next row follows the unrestricted Damerau-Levenshtein recurrence, same as the trie walk.
+    fn next_state(&self, id: usize, symbol: usize) -> Option<State> {
+        let state = &self.states[id];
+        let window = state.runes.len();
+        let prev = &state.rows[window];
+        let mut row: Vec<u8> = Vec::with_capacity(prev.len());
+        row.push((prev[0] as usize + 1).min(self.cap) as u8);
+
+        // the last column where the input matches the symbol
+        let mut last_col = 0;
+
+        for (j, input) in self.input.iter().enumerate().map(|(idx, input)| (idx + 1, input)) {
+            let cost = if *input == symbol { 0 } else { 1 };
+            let mut dist = (prev[j - 1] as usize + cost)
+                .min(prev[j] as usize + 1)
+                .min(row[j - 1] as usize + 1);
+
+            // `back` runes sit between the last match of this input in the window and the symbol
+            if last_col > 0 {
+                if let Some(back) = state.runes.iter().rev().position(|rune| rune == input) {
+                    let transposed = state.rows[window - 1 - back][last_col - 1] as usize
+                        + back + 1 + (j - last_col - 1);
+
+                    dist = dist.min(transposed);
+                }
+            }
+
+            if cost == 0 {
+                last_col = j;
+            }
+
+            row.push(dist.min(self.cap) as u8);
+        }
+
+        // the row minimum never decreases, so the state can't reach any word within `max_edit`
+        if row.iter().all(|dist| *dist as usize >= self.cap) {
+            return None;
+        }
+
+        let mut rows = state.rows[1..].to_vec();
+        rows.push(row);
+
+        let mut runes = state.runes.clone();
+        if window > 0 {
+            runes.remove(0);
+            runes.push(symbol);
+        }
+
+        Some(State { rows, runes })
+    }
+}
+
+/// Compile the query into its automaton, then intersect it with the dictionary trie.
+pub(crate) fn candidate(
+    word: String,
+    ctx: &Context,
+    tx_async: &channel::Sender<Candidate>
+) {
+    let word = word.trim().to_lowercase();
+    if word.is_empty() {
+        return;
+    }
+
+    let mut automaton = Automaton::new(&word, ctx.max_edit);
+
+    ctx.dict.intersect(&mut automaton, |found, score, edit| {
+        tx_async.send(Candidate::new(found.to_owned(), score, edit)).is_ok()
+    });
+}
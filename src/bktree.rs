@@ -0,0 +1,142 @@
+use crossbeam_channel as channel;
+
+use candidate::Candidate;
+use metric::{Damerau, Metric};
+use trie::Trie;
+
+/// A BK-tree over a word list: every child sits under the distance from its parent, so a search
+/// within `max_distance` of a query at distance `d` from a node only needs to visit the children
+/// filed under `d - max_distance ..= d + max_distance`. Works with any `Metric`, including the
+/// weighted ones the trie based modes can't express.
+pub struct BkTree<M: Metric = Damerau> {
+    metric: M,
+    nodes: Vec<BkNode>,
+}
+
+struct BkNode {
+    word: String,
+    score: u32,
+    children: Vec<(u32, usize)>,
+}
+
+impl<M: Metric> BkTree<M> {
+    pub fn new(metric: M) -> Self {
+        BkTree { metric, nodes: Vec::new() }
+    }
+
+    /// Build the tree from the `(word, score)` pairs.
+    pub fn from_words<I>(metric: M, words: I) -> Self
+        where I: IntoIterator<Item = (String, u32)>
+    {
+        let mut tree = BkTree::new(metric);
+        for (word, score) in words {
+            tree.insert(word, score);
+        }
+
+        tree
+    }
+
+    /// Add the `word` to the tree, or update its score if it's already there.
+    pub fn insert(&mut self, word: String, score: u32) {
+        if self.nodes.is_empty() {
+            self.nodes.push(BkNode { word, score, children: Vec::new() });
+            return;
+        }
+
+        let mut curr = 0;
+
+        loop {
+            let distance = self.metric.distance(&self.nodes[curr].word, &word);
+            if distance == 0 {
+                self.nodes[curr].score = score;
+                return;
+            }
+
+            match self.nodes[curr].children.iter().find(|(dist, _)| *dist == distance) {
+                Some((_, child)) => curr = *child,
+                None => {
+                    let id = self.nodes.len();
+
+                    self.nodes.push(BkNode { word, score, children: Vec::new() });
+                    self.nodes[curr].children.push((distance, id));
+                    return;
+                },
+            }
+        }
+    }
+
+    /// All the words within `max_distance` from the `word`, with the distance in
+    /// `Candidate::edit`, in no particular order. The `Candidate::operations` are always the
+    /// Damerau-Levenshtein edits, whichever the metric.
+    pub fn find(&self, word: &str, max_distance: u8) -> Vec<Candidate> {
+        let mut result = Vec::new();
+
+        self.walk(word, max_distance, |candidate| {
+            result.push(candidate.trace(word));
+            true
+        });
+
+        result
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Hand the words within `max_distance` to `found`, until it returns `false`.
+    fn walk<F>(&self, word: &str, max_distance: u8, mut found: F)
+        where F: FnMut(Candidate) -> bool
+    {
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let max_distance = u32::from(max_distance);
+        let mut stack = vec![0];
+
+        while let Some(curr) = stack.pop() {
+            let node = &self.nodes[curr];
+            let distance = self.metric.distance(&node.word, word);
+
+            if distance <= max_distance
+                && !found(Candidate::new(node.word.clone(), node.score, distance as u8)) {
+                return;
+            }
+
+            // triangle inequality: the words under `dist` are at least `|dist - distance|` away
+            let low = distance.saturating_sub(max_distance);
+            let high = distance + max_distance;
+
+            for (dist, child) in node.children.iter() {
+                if *dist >= low && *dist <= high {
+                    stack.push(*child);
+                }
+            }
+        }
+    }
+}
+
+impl BkTree<Damerau> {
+    /// The index for the `BkTree` mode, over the words in the dictionary.
+    pub(crate) fn build(dict: &Trie) -> Self {
+        BkTree::from_words(Damerau, dict.words())
+    }
+}
+
+pub(crate) fn candidate(
+    word: String,
+    max_edit: u8,
+    tree: &BkTree,
+    tx_async: &channel::Sender<Candidate>
+) {
+    let word = word.trim().to_lowercase();
+    if word.is_empty() {
+        return;
+    }
+
+    tree.walk(&word, max_edit, |candidate| tx_async.send(candidate).is_ok());
+}
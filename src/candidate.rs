@@ -1,16 +1,59 @@
-use std::cmp::Ordering;
 use std::hash::{Hash, Hasher};
 
+use operation::{self, Operation};
+
+/// How a candidate was found.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MatchKind {
+    /// Within the max edits from the input.
+    Edit,
+    /// Sounds like the input, however many edits away.
+    Phonetic,
+    /// The input split into known words, with a space inserted for each edit.
+    Split,
+    /// Adjacent words of a text joined into a known word.
+    Join,
+    /// Spelled in pinyin by the input, within the max edits, e.g. "zhongguo" for "中国".
+    Pinyin,
+}
+
 #[derive(Debug)]
 pub struct Candidate {
     pub word: String,
     pub score: u32,
     pub edit: u8,
+    /// The chance that this is the word meant by the input, among all the candidates of the
+    /// query, if the configured `Scorer` is a probabilistic one, e.g. the `NoisyChannel`.
+    pub probability: Option<f64>,
+    /// The edits turning the input into this word, positioned by the characters of the input.
+    /// Empty for the `Pinyin` matches, which aren't spelled with the runes of the input.
+    pub operations: Vec<Operation>,
+    /// How the candidate was found: within the max edits, by the phonetic index, as a split of
+    /// the input or a join of two words of a text, or by its pinyin, see `MatchKind`.
+    pub kind: MatchKind,
 }
 
 impl Candidate {
     pub(crate) fn new(word: String, score: u32, edit: u8) -> Self {
-        Candidate { word, score, edit }
+        Candidate {
+            word,
+            score,
+            edit,
+            probability: None,
+            operations: Vec::new(),
+            kind: MatchKind::Edit,
+        }
+    }
+
+    /// Attach the edits from the `input` to the candidate.
+    pub(crate) fn trace(mut self, input: &str) -> Self {
+        // a word of another script than the input isn't made of edits of it
+        if self.kind == MatchKind::Pinyin {
+            return self;
+        }
+
+        self.operations = operation::operations(input, &self.word);
+        self
     }
 
     pub fn get_word(&self) -> String {
@@ -24,27 +67,13 @@ impl Clone for Candidate {
             word: self.word.clone(),
             score: self.score,
             edit: self.edit,
+            probability: self.probability,
+            operations: self.operations.clone(),
+            kind: self.kind,
         }
     }
 }
 
-impl Ord for Candidate {
-    fn cmp(&self, other: &Candidate) -> Ordering {
-        //TODO: use calculations
-        if self.edit == other.edit {
-            self.score.cmp(&other.score)
-        } else {
-            other.edit.cmp(&self.edit)
-        }
-    }
-}
-
-impl PartialOrd for Candidate {
-    fn partial_cmp(&self, other: &Candidate) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
-}
-
 impl PartialEq for Candidate {
     fn eq(&self, other: &Candidate) -> bool {
         self.word == other.word
@@ -0,0 +1,68 @@
+/// The letter case pattern of a typed word, to re-case its corrections the same way.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(crate) enum Case {
+    /// "hello"
+    Lower,
+    /// "Hello", and the single capitals such as "I".
+    Title,
+    /// "HELLO"
+    Upper,
+    /// Any other pattern, e.g. "iPhone", kept as the capitals at each position.
+    Mixed(Vec<bool>),
+}
+
+impl Case {
+    pub(crate) fn of(word: &str) -> Self {
+        let capitals: Vec<bool> = word.chars().map(char::is_uppercase).collect();
+        let letters = word.chars().filter(|rune| rune.is_alphabetic()).count();
+        let upper = capitals.iter().filter(|capital| **capital).count();
+
+        if upper == 0 {
+            Case::Lower
+        } else if upper == letters && letters > 1 {
+            Case::Upper
+        } else if upper == 1 && capitals[0] {
+            Case::Title
+        } else {
+            Case::Mixed(capitals)
+        }
+    }
+
+    /// The lowercase `word` in this case pattern.
+    pub(crate) fn apply(&self, word: &str) -> String {
+        match self {
+            Case::Lower => word.to_owned(),
+            Case::Upper => word.to_uppercase(),
+            Case::Title => {
+                let mut runes = word.chars();
+                match runes.next() {
+                    Some(first) => first.to_uppercase().chain(runes).collect(),
+                    None => String::new(),
+                }
+            },
+            Case::Mixed(capitals) => {
+                let mut result = String::with_capacity(word.len());
+
+                for (pos, rune) in word.chars().enumerate() {
+                    if capitals.get(pos).cloned().unwrap_or(false) {
+                        result.extend(rune.to_uppercase());
+                    } else {
+                        result.push(rune);
+                    }
+                }
+
+                result
+            },
+        }
+    }
+}
+
+/// The `word` cased after the `input` it corrects, unless the dictionary spells it with
+/// capitals of its own, e.g. "NASA" or "London", which are kept, but for an all capitals input.
+pub(crate) fn recase(input: &str, word: &str, canonical: Option<&str>) -> String {
+    match (canonical, Case::of(input)) {
+        (Some(_), Case::Upper) => word.to_uppercase(),
+        (Some(canonical), _) => canonical.to_owned(),
+        (None, case) => case.apply(word),
+    }
+}
@@ -1,24 +1,244 @@
 #![allow(unreachable_patterns)]
 
+use std::cmp::Ordering;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, ErrorKind};
 use std::path::PathBuf;
 use std::str::Chars;
-use std::cmp::Ordering;
 
 use crossbeam_channel as channel;
-use crate::AutoCorrect;
+use hashbrown::HashMap;
 use crate::candidate::Candidate;
-use crate::config::Config;
-use crate::stores;
+use crate::config::DictSource;
+use crate::error::Error;
+use crate::stores::Store;
 use crate::SupportedLocale;
-use crate::support::en_us;
-use crate::trie::{check_bitmap, Node};
+use crate::support::{de, en_gb, en_us, es, fr, it};
+use crate::trie::Trie;
+
+pub static DELIM: &str = ",";
+
+pub(crate) fn open_dict(source: DictSource) -> Result<Box<dyn BufRead + Send>, Error> {
+    match source {
+        DictSource::Embedded(content) => Ok(Box::new(content.as_bytes())),
+        DictSource::File(dict_path) => open_path(&dict_path, Error::MissingDictionary),
+    }
+}
+
+/// Open a file the service loads besides the dictionary, e.g. the language model.
+pub(crate) fn open_file(path: &str) -> Result<Box<dyn BufRead + Send>, Error> {
+    open_path(path, Error::MissingFile)
+}
+
+/// Open the file at the `path`: a file that isn't there is reported by the `missing` error,
+/// and one that is there but can't be opened, e.g. for its permissions, as an `Io` error.
+fn open_path(path: &str, missing: fn(String) -> Error) -> Result<Box<dyn BufRead + Send>, Error> {
+    if path.is_empty() {
+        return Err(missing(String::from("(empty)")));
+    }
+
+    match File::open(PathBuf::from(path)) {
+        Ok(file) => Ok(Box::new(BufReader::new(file))),
+        Err(ref err) if err.kind() == ErrorKind::NotFound => Err(missing(path.to_owned())),
+        Err(err) => Err(Error::io(path, err)),
+    }
+}
+
+/// Send the dictionary lines, paired with their 1-based line numbers, to the receiving end. A
+/// line that can't be read, e.g. one that isn't valid UTF-8, is sent as the `Io` error of the
+/// `path`, and ends the loading, so the dictionary is never loaded only in part.
+pub(crate) fn load_dict_async(
+    reader: Box<dyn BufRead + Send>,
+    path: String,
+    tx: channel::Sender<Result<(usize, String), Error>>
+) {
+    for (idx, line) in reader.lines().enumerate() {
+        let (stop, received) = match line {
+            Ok(line) => (false, Ok((idx + 1, line))),
+            Err(err) => (true, Err(Error::io(&path, err))),
+        };
+
+        if tx.send(received).is_err() || stop {
+            return;
+        }
+    }
+}
+
+/// The Damerau-Levenshtein distance between the 2 words: the least number of inserts, deletes,
+/// replaces and transposes of adjacent characters that turns one word into the other.
+pub(crate) fn damerau_distance(source: &str, target: &str) -> usize {
+    let a: Vec<char> = source.chars().collect();
+    let b: Vec<char> = target.chars().collect();
+    let (n, m) = (a.len(), b.len());
+    let max = n + m;
+
+    // the table is shifted by 1 row and 1 column, which are filled with the sentinel `max`
+    let mut table = vec![vec![0usize; m + 2]; n + 2];
+    table[0][0] = max;
+
+    for (i, row) in table.iter_mut().enumerate().skip(1) {
+        row[0] = max;
+        row[1] = i - 1;
+    }
+
+    for cell in table[0].iter_mut().skip(1) {
+        *cell = max;
+    }
+
+    for (j, cell) in table[1].iter_mut().enumerate().skip(1) {
+        *cell = j - 1;
+    }
+
+    // the last row where each character has been seen in the source
+    let mut last_row: HashMap<char, usize> = HashMap::new();
+
+    for i in 1..=n {
+        let mut last_col = 0;
+
+        for j in 1..=m {
+            let i1 = last_row.get(&b[j - 1]).cloned().unwrap_or(0);
+            let j1 = last_col;
+
+            let cost = if a[i - 1] == b[j - 1] {
+                last_col = j;
+                0
+            } else {
+                1
+            };
+
+            table[i + 1][j + 1] = (table[i][j] + cost)
+                .min(table[i + 1][j] + 1)
+                .min(table[i][j + 1] + 1)
+                .min(table[i1][j1] + (i - i1 - 1) + 1 + (j - j1 - 1));
+        }
+
+        last_row.insert(a[i - 1], i);
+    }
+
+    table[n + 1][m + 1]
+}
+
+/// The words of the `text`, with their byte offsets: the runs of letters, including the
+/// apostrophes within them, e.g. "don't", and the hyphens with letters on both sides, e.g.
+/// "x-ray", but not the dashes in "pro - con" or "pro--con".
+pub(crate) fn tokenize(text: &str) -> Vec<(usize, &str)> {
+    let mut tokens = Vec::new();
+    let mut start: Option<usize> = None;
+
+    let mut push = |from: usize, to: usize| {
+        let token = text[from..to].trim_start_matches('\'');
+        let offset = to - token.len();
+        let token = token.trim_end_matches('\'');
+
+        if !token.is_empty() {
+            tokens.push((offset, token));
+        }
+    };
+
+    let runes: Vec<(usize, char)> = text.char_indices().collect();
+
+    for (idx, &(pos, rune)) in runes.iter().enumerate() {
+        let inner_hyphen = rune == '-'
+            && idx > 0
+            && runes[idx - 1].1.is_alphabetic()
+            && runes.get(idx + 1).is_some_and(|(_, next)| next.is_alphabetic());
+
+        match (rune.is_alphabetic() || rune == '\'' || inner_hyphen, start) {
+            (true, None) => start = Some(pos),
+            (false, Some(from)) => {
+                push(from, pos);
+                start = None;
+            },
+            _ => {},
+        }
+    }
+
+    if let Some(from) = start {
+        push(from, text.len());
+    }
+
+    tokens
+}
+
+/// The word and the score of a dictionary line, either `word,score`, or `word score` as in the
+/// `ZhCn` dictionary.
+pub(crate) fn split_entry(line: &str) -> (&str, Option<&str>) {
+    if let Some((word, score)) = line.split_once(DELIM) {
+        return (word, Some(score));
+    }
+
+    match line.trim().rsplit_once(char::is_whitespace) {
+        Some((word, score)) => (word.trim_end(), Some(score)),
+        None => (line, None),
+    }
+}
+
+/// All the variations within 1 edit distance from the `word`, made of the deletes, transposes,
+/// replaces and inserts with the locale's alphabet.
+pub(crate) fn edits1(word: &str, locale: SupportedLocale) -> Vec<String> {
+    let runes: Vec<char> = word.chars().collect();
+    let len = runes.len();
+    let mut result = Vec::with_capacity(54 * len + 26);
+
+    let build = |left: &[char], mid: &[char], right: &[char]| -> String {
+        left.iter().chain(mid.iter()).chain(right.iter()).collect()
+    };
+
+    for pos in 0..=len {
+        if pos < len {
+            // deletes
+            if len > 1 {
+                result.push(build(&runes[..pos], &[], &runes[pos + 1..]));
+            }
+
+            // transposes
+            if pos + 1 < len && runes[pos] != runes[pos + 1] {
+                result.push(build(&runes[..pos], &[runes[pos + 1], runes[pos]], &runes[pos + 2..]));
+            }
 
-pub static DELIM: &'static str = ",";
-pub static DEFAULT_LOCALE: &'static str = "en-us";
+            // replaces
+            for rune in get_char_set(locale) {
+                if rune != runes[pos] {
+                    result.push(build(&runes[..pos], &[rune], &runes[pos + 1..]));
+                }
+            }
+        }
+
+        // inserts
+        for rune in get_char_set(locale) {
+            result.push(build(&runes[..pos], &[rune], &runes[pos..]));
+        }
+    }
 
+    result
+}
+
+fn get_char_set(locale: SupportedLocale) -> Chars<'static> {
+    match locale {
+        SupportedLocale::EnUs => en_us::ALPHABET_EN.chars(),
+        SupportedLocale::EnGb => en_gb::ALPHABET_EN.chars(),
+        SupportedLocale::De => de::ALPHABET_DE.chars(),
+        SupportedLocale::Fr => fr::ALPHABET_FR.chars(),
+        SupportedLocale::Es => es::ALPHABET_ES.chars(),
+        SupportedLocale::It => it::ALPHABET_IT.chars(),
+        // the characters are replaced by those sounding alike instead, see `pinyin`
+        SupportedLocale::ZhCn => "".chars(),
+    }
+}
+
+/// The letters the fan-out inserts and replaces with, which only searches the lowercase ASCII
+/// words.
+const FAN_OUT_LETTERS: [&str; 26] = [
+    "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n", "o", "p", "q", "r", "s",
+    "t", "u", "v", "w", "x", "y", "z"
+];
+
+/// Send the inserts and replaces of the `word` for the fan-out: the known ones as the
+/// candidates of the `current_edit`, and the unvisited ones to search from at the next edit.
+/// The `marker` holds the positions already edited, which are skipped.
 pub(crate) fn ins_repl(
+    dict: &Trie,
+    store: &Store,
     word: &str,
     current_edit: u8,
     tx_curr: channel::Sender<Candidate>,
@@ -30,15 +250,24 @@ pub(crate) fn ins_repl(
         return;
     }
 
-    let len = en_us::ALPHABET.len();
+    // the bitmap can only reject the runes placed by the last edit, since any further edit may
+    // still shift them to another position
+    let last_edit = tx_next.is_none();
 
-    for idx in 0..len {
-        let rune = en_us::ALPHABET[idx];
-        let rune_code = get_char_code(rune, 0);
+    for (idx, rune) in FAN_OUT_LETTERS.iter().enumerate() {
+        let rune_code = idx as u8;
 
         for pos in 0..=size {
-            if check_bit(marker, pos) || !check_bitmap(rune_code, pos) {
-                // early termination for duplicate or unrealistic cases
+            if check_bit(marker, pos) {
+                // early termination for duplicate cases
+                continue;
+            }
+
+            // early termination for unrealistic cases
+            let can_insert = !last_edit || dict.check_bitmap(rune_code, pos);
+            let can_replace = pos > 0 && (!last_edit || dict.check_bitmap(rune_code, pos - 1));
+
+            if !can_insert && !can_replace {
                 continue;
             }
 
@@ -53,35 +282,40 @@ pub(crate) fn ins_repl(
                     }
                 };
 
-                if pos == 1 && rune.cmp(left) == Ordering::Equal {
+                if pos == 1 && rune.cmp(&left) == Ordering::Equal {
                     // insert at pos 0 has already handled this case
                     continue;
                 }
 
-                if size > 2 && pos == size - 1 && rune.cmp(right) == Ordering::Equal {
+                if size > 2 && pos == size - 1 && rune.cmp(&right) == Ordering::Equal {
                     // insert at pos (size - 1) has already handled this case
                     continue;
                 }
 
                 // insert
-                send_one([left, rune, right].join(""),
-                         current_edit, &tx_curr, &tx_next, mark_bit(marker, pos, true));
+                if can_insert {
+                    send_one(dict, store, [left, rune, right].join(""),
+                             current_edit, &tx_curr, &tx_next, mark_bit(marker, pos, true));
+                }
 
                 // replace
-                if rune_code != get_char_code(word, pos - 1) {
-                    send_one([&left[..pos - 1], rune, right].join(""),
+                if can_replace && byte_at(rune, 0) != byte_at(word, pos - 1) {
+                    send_one(dict, store, [&left[..pos - 1], rune, right].join(""),
                              current_edit, &tx_curr, &tx_next, mark_bit(marker, pos, false));
                 }
             } else {
                 // if pos == 0, just insert
-                send_one([rune, word].join(""),
+                send_one(dict, store, [rune, word].join(""),
                          current_edit, &tx_curr, &tx_next, mark_bit(marker, pos, true));
             }
         }
     }
 }
 
+/// Send the deletes and transposes of the `word` for the fan-out, same as `ins_repl`.
 pub(crate) fn del_tran(
+    dict: &Trie,
+    store: &Store,
     word: &str,
     current_edit: u8,
     tx_curr: channel::Sender<Candidate>,
@@ -101,23 +335,25 @@ pub(crate) fn del_tran(
                 (&word[..size - 1], &word[size - 1..size], "")
             };
 
-        if pos < size && del.cmp(&right[..1]) == Ordering::Equal{
+        if pos < size && del.cmp(&right[..1]) == Ordering::Equal {
             continue;
         }
 
         // delete
-        send_one([left, right].join(""),
+        send_one(dict, store, [left, right].join(""),
                  current_edit, &tx_curr, &tx_next, marker);
 
         // transpose
         if pos < size {
-            send_one([left, &right[..1], del, &right[1..]].join(""),
+            send_one(dict, store, [left, &right[..1], del, &right[1..]].join(""),
                      current_edit, &tx_curr, &tx_next, marker);
         }
     }
 }
 
 fn send_one(
+    dict: &Trie,
+    store: &Store,
     target: String,
     edit: u8,
     chan: &channel::Sender<Candidate>,
@@ -125,7 +361,7 @@ fn send_one(
     marker: u32
 ) {
     if let Some(next_chan) = tx_next {
-        if !stores::contains(&target) {
+        if !store.contains(&target, edit) {
             next_chan
                 .send((target.clone(), marker))
                 .unwrap_or_else(|err| {
@@ -134,7 +370,7 @@ fn send_one(
         }
     }
 
-    if let Some(score) = Node::check(&target) {
+    if let Some(score) = dict.check(&target) {
         chan
             .send(Candidate::new(target, score, edit))
             .unwrap_or_else(|err| {
@@ -143,100 +379,8 @@ fn send_one(
     }
 }
 
-pub(crate) fn find_variations(
-    word: String,
-    locale: SupportedLocale,
-) -> channel::Receiver<String>
-{
-    let (tx, rx) = channel::unbounded();
-
-    AutoCorrect::run_job(move || {
-        let len = word.len() + 1;
-        for pos in 0..len {
-            variations_at_pos(word.clone(), pos, len, locale, &tx);
-        }
-    });
-
-    rx
-}
-
-pub(crate) fn load_dict_async(dict_path: String, tx: channel::Sender<String>) {
-    if dict_path.is_empty() {
-        eprintln!("No dictionary path is given");
-        return;
-    }
-
-    let file_loc = PathBuf::from(dict_path);
-    if !file_loc.exists() || !file_loc.is_file() {
-        eprintln!("Given dictionary path is invalid: {:?}", file_loc);
-        return;
-    }
-
-    let file = File::open(file_loc).expect("file not found");
-    let reader = BufReader::new(file);
-
-    for raw_line in reader.lines() {
-        if let Ok(line) = raw_line {
-            if let Err(_) = tx.send(line) {
-                return;
-            }
-        }
-    }
-}
-
-fn get_char_code(word: &str, pos: usize) -> &u8 {
-    match word.as_bytes().get(pos) {
-        Some(res) => res,
-        None => &0u8,
-    }
-}
-
-fn variations_at_pos(
-    word: String,
-    pos: usize,
-    len: usize,
-    locale: SupportedLocale,
-    tx: &channel::Sender<String>
-)
-{
-    if pos >= len {
-        return;
-    }
-
-    let mut remove_base = word.clone();
-    let mut removed = '\u{0000}';
-
-    if pos < len - 1 && remove_base.len() > 1 {
-        // deletes
-        removed = remove_base.remove(pos);
-        tx.send(remove_base.clone()).expect("Failed to send the search result...");
-    }
-
-    for rune in get_char_set(locale) {
-        // inserts
-        let mut base = word.clone();
-        base.insert(pos, rune);
-        tx.send(base).expect("Failed to send the search result...");
-
-        // replaces if we've actually removed a char
-        if removed != '\u{0000}' && rune != removed {
-            let mut replace = remove_base.clone();
-            replace.insert(pos, rune);
-            tx.send(replace).expect("Failed to send the search result...");
-        }
-    }
-
-    // transpose: if we've removed
-    if removed != '\u{0000}' && pos > 0 {
-        remove_base.insert(pos - 1, removed);
-        tx.send(remove_base.clone()).expect("Failed to send the search result...");
-    }
-}
-
-fn get_char_set(locale: SupportedLocale) -> Chars<'static> {
-    match locale {
-        SupportedLocale::EnUs => en_us::ALPHABET_EN.chars(),
-    }
+fn byte_at(word: &str, pos: usize) -> u8 {
+    word.as_bytes().get(pos).cloned().unwrap_or(0)
 }
 
 fn mark_bit(source: u32, pos: usize, insert: bool) -> u32 {
@@ -262,167 +406,5 @@ fn mark_bit(source: u32, pos: usize, insert: bool) -> u32 {
 }
 
 fn check_bit(source: u32, pos: usize) -> bool {
-    source & (1 << pos) > 0
+    pos < 32 && source & (1 << pos) > 0
 }
-
-pub(crate) mod deprecated {
-    use crossbeam_channel as channel;
-    use hashbrown::HashMap;
-    use crate::AutoCorrect;
-    use crate::candidate::Candidate;
-    use crate::config::AutoCorrectConfig;
-    use crate::support::en_us;
-    use super::*;
-
-    pub(crate) fn delete_n_replace(
-        word: String,
-        set: &HashMap<String, u32>,
-        current_edit: u8,
-        tx_curr: channel::Sender<Candidate>,
-        tx_next: Option<channel::Sender<String>>,
-    )
-    {
-        let mut base: String;
-        let mut replace: String;
-        let mut removed: char = '\u{0001}';
-        let mut last_removed: char;
-
-        // deletes
-        for pos in 0..word.len() {
-            base = word.clone();
-
-            last_removed = removed;
-            removed = base.remove(pos);
-
-            if tx_next.is_some() && last_removed != removed {
-                send_next_string(base.clone(), &tx_next);
-            }
-
-            // replaces
-            for rune in en_us::ALPHABET_EN.chars() {
-                if rune == removed {
-                    continue;
-                }
-
-                replace = base.clone();
-                replace.insert(pos, rune);
-
-                if tx_next.is_some() {
-                    send_next_string(replace.clone(), &tx_next);
-                }
-
-                if set.contains_key(&replace) {
-                    let score = set[&replace];
-                    send_candidate(Candidate::new(replace, score, current_edit), &tx_curr);
-                }
-            }
-
-            if set.contains_key(&base) {
-                let score = set[&base];
-                send_candidate(Candidate::new(base, score, current_edit), &tx_curr);
-            }
-        }
-    }
-
-    pub(crate) fn transpose_n_insert(
-        word: String,
-        set: &HashMap<String, u32>,
-        current_edit: u8,
-        tx_curr: channel::Sender<Candidate>,
-        tx_next: Option<channel::Sender<String>>,
-    )
-    {
-        let edit_two = tx_next.is_some();
-
-        let mut base: String;
-        let mut removed: char = '\u{0001}';
-        let mut last_removed: char;
-
-        // transposes
-        let len = word.len() + 1;
-        for pos in 0..len {
-            if pos > 0 && pos < len - 1 {
-                base = word.clone();
-
-                last_removed = removed;
-                removed = base.remove(pos);
-
-                if last_removed == removed {
-                    continue;
-                }
-
-                base.insert(pos - 1, removed);
-
-                if edit_two && !base.is_empty() {
-                    send_next_string(base.clone(), &tx_next);
-                }
-
-                if set.contains_key(&base) {
-                    let score = set[&base];
-                    send_candidate(Candidate::new(base, score, current_edit), &tx_curr);
-                }
-            }
-
-            // inserts
-            for rune in en_us::ALPHABET_EN.chars() {
-                base = word.clone();
-                base.insert(pos, rune);
-
-                if edit_two {
-                    send_next_string(base.clone(), &tx_next);
-                }
-
-                if set.contains_key(&base) {
-                    let score = set[&base];
-                    send_candidate(Candidate::new(base, score, current_edit), &tx_curr);
-                }
-            }
-        }
-    }
-
-    fn send_candidate(candidate: Candidate, tx: &channel::Sender<Candidate>,) {
-        tx.send(candidate).expect("Failed to return a candidate");
-    }
-
-    fn send_next_string(word: String, tx: &Option<channel::Sender<String>>) {
-        if let Some(tx_next) = tx {
-            tx_next.send(word).unwrap_or_else(|err| {
-                eprintln!("Failed to search the string: {:?}", err);
-            });
-        }
-    }
-
-    fn generate_reverse_dict(config: &Config) -> HashMap<String, Vec<String>> {
-        let mut result: HashMap<String, Vec<String>> = HashMap::new();
-
-        // one worker to read from file
-        let (tx, rx) = channel::unbounded();
-        let dict_path = config.get_dict_path();
-        let locale = config.get_locale();
-
-        AutoCorrect::run_job(move || {
-            load_dict_async(dict_path, tx);
-        });
-
-        // one worker to write to memory
-        for word in rx {
-            for variation in find_variations(word.clone(), locale) {
-                update_reverse_dict(word.clone(), variation, &mut result);
-            }
-        }
-
-        result
-    }
-
-    fn update_reverse_dict(word: String, variation: String, dict: &mut HashMap<String, Vec<String>>) {
-        if let Some(vec) = dict.get_mut(&variation) {
-            if !vec.contains(&word) {
-                vec.push(word);
-            }
-
-            return;
-        }
-
-        dict.insert(variation, vec![word]);
-    }
-}
\ No newline at end of file
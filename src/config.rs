@@ -1,18 +1,64 @@
 #![allow(unreachable_patterns)]
 
-const DEFAULT_MAX_EDIT: u8 = 1;
+use std::sync::Arc;
+
+use decision::DecisionPolicy;
+use error::Error;
+use phonetic::Phonetic;
+use scorer::{EditsThenFrequency, Scorer};
+
 const MAX_EDIT_THRESHOLD: u8 = 3;
 const POOL_SIZE: usize = 12;
 
 #[derive(PartialEq, Eq, Clone, Copy)]
 pub enum SupportedLocale {
     EnUs,
+    EnGb,
+    /// German, with the compounds of known words accepted, and the words written apart joined
+    /// into them, by default.
+    De,
+    Fr,
+    Es,
+    It,
+    /// Simplified Chinese, corrected by the pinyin of the words rather than by the edits of
+    /// their characters: a word typed in pinyin finds the words it spells, and a word typed
+    /// in characters finds the words sounding like it.
+    ZhCn,
 }
 
 #[derive(PartialEq, Eq, Clone, Copy)]
 pub enum RunMode {
+    /// Answer from the precomputed one-edit neighbors of the known words.
     SpeedSensitive,
+    /// Walk the dictionary trie, pruning the branches already too far from the input.
     SpaceSensitive,
+    /// Answer from the index of the delete-only variations of the known words, generating only
+    /// the deletes of the input, which is much faster for 2 or 3 edits. The index is built
+    /// when the dictionary is loaded, which takes longer and more memory than the other modes.
+    SymmetricDelete,
+    /// Compile the input into a Damerau-Levenshtein automaton, and intersect it with the
+    /// dictionary trie. Same results as the `SpaceSensitive` mode.
+    Automaton,
+    /// Search a BK-tree built from the dictionary by the Damerau-Levenshtein distance. Same
+    /// results as the `SpaceSensitive` mode.
+    BkTree,
+}
+
+/// Where the dictionary shall be loaded from: either the copy compiled into the binary with the
+/// locale's cargo feature, or an external file.
+pub(crate) enum DictSource {
+    Embedded(&'static str),
+    File(String),
+}
+
+impl DictSource {
+    /// The path of the file, or the given name of the embedded copy, to report the errors with.
+    pub(crate) fn describe(&self, embedded: &str) -> String {
+        match self {
+            DictSource::Embedded(_) => embedded.to_owned(),
+            DictSource::File(path) => path.to_owned(),
+        }
+    }
 }
 
 pub struct Config {
@@ -21,6 +67,15 @@ pub struct Config {
     locale: SupportedLocale,
     run_mode: RunMode,
     override_dict: String,
+    scorer: Arc<dyn Scorer>,
+    phonetic: Option<Phonetic>,
+    language_model: String,
+    confusion_sets: String,
+    decision_policy: DecisionPolicy,
+    // unset toggles fall back to the locale's defaults, see `LocaleDefaults`
+    split_words: Option<bool>,
+    join_words: Option<bool>,
+    compound_words: Option<bool>,
 }
 
 impl Config {
@@ -43,24 +98,99 @@ impl Config {
         override_dict: &str
     ) -> Config {
         Config {
-            max_edit: normalize_max_edit(max_edit),
+            max_edit,
             pool_size,
             locale,
             run_mode,
             override_dict: override_dict.to_owned(),
+            scorer: Arc::new(EditsThenFrequency),
+            phonetic: None,
+            language_model: String::new(),
+            confusion_sets: String::new(),
+            decision_policy: DecisionPolicy::default(),
+            split_words: None,
+            join_words: None,
+            compound_words: None,
+        }
+    }
+
+    /// Same as `new_with_params`, but reject a `max_edit` or `pool_size` out of the supported
+    /// range right away.
+    pub fn try_new_with_params(
+        max_edit: u8,
+        pool_size: usize,
+        locale: SupportedLocale,
+        run_mode: RunMode,
+        override_dict: &str
+    ) -> Result<Config, Error> {
+        let config = Config::new_with_params(max_edit, pool_size, locale, run_mode, override_dict);
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        check_max_edit(self.max_edit)?;
+
+        if self.pool_size == 0 {
+            return Err(Error::InvalidConfig(String::from("the pool size must be greater than 0")));
+        }
+
+        self.get_dict_source().map(|_| ())
+    }
+
+    /// Bring a `max_edit` out of the supported range back into it, logging the invalid value.
+    pub(crate) fn normalize(&mut self) {
+        self.max_edit = normalize_max_edit(self.max_edit);
+    }
+
+    /// The dictionary to load: the override one if set, else the locale's bundled one, which
+    /// the locales shipped without a dictionary don't have.
+    pub(crate) fn get_dict_source(&self) -> Result<DictSource, Error> {
+        if !self.override_dict.is_empty() {
+            return Ok(DictSource::File(self.override_dict.to_owned()));
+        }
+
+        if !bundles_dict(self.locale) {
+            return Err(Error::InvalidConfig(String::from("no bundled dictionary; set override_dict")));
+        }
+
+        match embedded_dict(self.locale) {
+            Some(content) => Ok(DictSource::Embedded(content)),
+            None => Ok(DictSource::File(self.get_dict_path())),
+        }
+    }
+
+    /// The precomputed one-edit neighbor index used by the `SpeedSensitive` mode. An override
+    /// dictionary comes without one, in which case the index shall be computed from the loaded
+    /// words.
+    pub(crate) fn get_neighbors_source(&self) -> Option<DictSource> {
+        if !self.override_dict.is_empty()
+            || !bundles_dict(self.locale)
+            || self.locale == SupportedLocale::ZhCn
+        {
+            return None;
+        }
+
+        match embedded_neighbors(self.locale) {
+            Some(content) => Some(DictSource::Embedded(content)),
+            None => Some(DictSource::File(
+                format!("./resources/{}/freq_50k_preproc.txt", locale_dir(self.locale))
+            )),
+        }
+    }
+
+    /// The readings of the characters, which the `ZhCn` locale corrects the words by.
+    pub(crate) fn get_pinyin_source(&self) -> DictSource {
+        match embedded_pinyin() {
+            Some(content) => DictSource::Embedded(content),
+            None => DictSource::File(String::from("./resources/zh-cn/pinyin.txt")),
         }
     }
 
     pub fn get_dict_path(&self) -> String {
         if self.override_dict.is_empty() {
-            let locale = match self.locale {
-                SupportedLocale::EnUs => "en-us"
-            };
-
-            match self.run_mode {
-                RunMode::SpeedSensitive => format!("./resources/{}/freq_50k_preproc.txt", locale),
-                RunMode::SpaceSensitive => format!("./resources/{}/freq_50k.txt", locale),
-            }
+            format!("./resources/{}/{}", locale_dir(self.locale), dict_file(self.locale))
         } else {
             self.override_dict.to_owned()
         }
@@ -84,6 +214,22 @@ pub trait AutoCorrectConfig {
     fn get_run_mode(&self) -> RunMode;
     fn set_override_dict(&mut self, dict_path: &str);
     fn get_override_dict(&self) -> String;
+    fn set_scorer(&mut self, scorer: Arc<dyn Scorer>);
+    fn get_scorer(&self) -> Arc<dyn Scorer>;
+    fn set_phonetic(&mut self, phonetic: Option<Phonetic>);
+    fn get_phonetic(&self) -> Option<Phonetic>;
+    fn set_language_model(&mut self, model_path: &str);
+    fn get_language_model(&self) -> String;
+    fn set_confusion_sets(&mut self, sets_path: &str);
+    fn get_confusion_sets(&self) -> String;
+    fn set_decision_policy(&mut self, policy: DecisionPolicy);
+    fn get_decision_policy(&self) -> DecisionPolicy;
+    fn set_split_words(&mut self, split: bool);
+    fn get_split_words(&self) -> bool;
+    fn set_join_words(&mut self, join: bool);
+    fn get_join_words(&self) -> bool;
+    fn set_compound_words(&mut self, compound: bool);
+    fn get_compound_words(&self) -> bool;
 }
 
 impl AutoCorrectConfig for Config {
@@ -134,31 +280,202 @@ impl AutoCorrectConfig for Config {
     fn get_override_dict(&self) -> String {
         self.override_dict.to_owned()
     }
+
+    fn set_scorer(&mut self, scorer: Arc<dyn Scorer>) {
+        self.scorer = scorer;
+    }
+
+    #[inline]
+    fn get_scorer(&self) -> Arc<dyn Scorer> {
+        Arc::clone(&self.scorer)
+    }
+
+    fn set_phonetic(&mut self, phonetic: Option<Phonetic>) {
+        self.phonetic = phonetic;
+    }
+
+    #[inline]
+    fn get_phonetic(&self) -> Option<Phonetic> {
+        self.phonetic
+    }
+
+    fn set_language_model(&mut self, model_path: &str) {
+        self.language_model = model_path.to_owned();
+    }
+
+    #[inline]
+    fn get_language_model(&self) -> String {
+        self.language_model.to_owned()
+    }
+
+    fn set_confusion_sets(&mut self, sets_path: &str) {
+        self.confusion_sets = sets_path.to_owned();
+    }
+
+    #[inline]
+    fn get_confusion_sets(&self) -> String {
+        self.confusion_sets.to_owned()
+    }
+
+    fn set_decision_policy(&mut self, policy: DecisionPolicy) {
+        self.decision_policy = policy;
+    }
+
+    #[inline]
+    fn get_decision_policy(&self) -> DecisionPolicy {
+        self.decision_policy
+    }
+
+    fn set_split_words(&mut self, split: bool) {
+        self.split_words = Some(split);
+    }
+
+    #[inline]
+    fn get_split_words(&self) -> bool {
+        self.split_words.unwrap_or_else(|| LocaleDefaults::of(self.locale).split_words)
+    }
+
+    fn set_join_words(&mut self, join: bool) {
+        self.join_words = Some(join);
+    }
+
+    #[inline]
+    fn get_join_words(&self) -> bool {
+        self.join_words.unwrap_or_else(|| LocaleDefaults::of(self.locale).join_words)
+    }
+
+    fn set_compound_words(&mut self, compound: bool) {
+        self.compound_words = Some(compound);
+    }
+
+    #[inline]
+    fn get_compound_words(&self) -> bool {
+        self.compound_words.unwrap_or_else(|| LocaleDefaults::of(self.locale).compound_words)
+    }
 }
 
 impl Clone for Config {
     fn clone(&self) -> Self {
-        Config::new_with_params(
+        let mut config = Config::new_with_params(
             self.max_edit,
             self.pool_size,
             self.locale,
             self.run_mode,
             &self.override_dict[..]
-        )
+        );
+
+        config.set_scorer(self.get_scorer());
+        config.set_phonetic(self.get_phonetic());
+        config.set_language_model(&self.language_model[..]);
+        config.set_confusion_sets(&self.confusion_sets[..]);
+        config.set_decision_policy(self.decision_policy);
+        // copied as they are, so the unset toggles keep following the locale
+        config.split_words = self.split_words;
+        config.join_words = self.join_words;
+        config.compound_words = self.compound_words;
+        config
     }
 }
 
+fn locale_dir(locale: SupportedLocale) -> &'static str {
+    match locale {
+        SupportedLocale::EnUs => "en-us",
+        SupportedLocale::EnGb => "en-gb",
+        SupportedLocale::De => "de",
+        SupportedLocale::Fr => "fr",
+        SupportedLocale::Es => "es",
+        SupportedLocale::It => "it",
+        SupportedLocale::ZhCn => "zh-cn",
+    }
+}
+
+/// Only the English and Chinese locales ship their dictionary with the crate, the others load
+/// the one supplied by the user.
+fn bundles_dict(locale: SupportedLocale) -> bool {
+    matches!(locale, SupportedLocale::EnUs | SupportedLocale::EnGb | SupportedLocale::ZhCn)
+}
+
+/// The toggles of a locale, for those not set on the config.
+struct LocaleDefaults {
+    split_words: bool,
+    join_words: bool,
+    compound_words: bool,
+}
+
+impl LocaleDefaults {
+    fn of(locale: SupportedLocale) -> Self {
+        match locale {
+            // the compounds are written as one word, so the known words are joined rather than
+            // split, and the unknown words made of known ones are taken as they are
+            SupportedLocale::De => LocaleDefaults {
+                split_words: false,
+                join_words: true,
+                compound_words: true,
+            },
+            _ => LocaleDefaults {
+                split_words: false,
+                join_words: false,
+                compound_words: false,
+            },
+        }
+    }
+}
+
+fn dict_file(locale: SupportedLocale) -> &'static str {
+    match locale {
+        SupportedLocale::ZhCn => "freq.txt",
+        _ => "freq_50k.txt",
+    }
+}
+
+fn embedded_dict(locale: SupportedLocale) -> Option<&'static str> {
+    match locale {
+        #[cfg(feature = "en-us")]
+        SupportedLocale::EnUs => Some(crate::support::en_us::DICT),
+        #[cfg(feature = "en-gb")]
+        SupportedLocale::EnGb => Some(crate::support::en_gb::DICT),
+        #[cfg(feature = "zh-cn")]
+        SupportedLocale::ZhCn => Some(crate::support::zh_cn::DICT),
+        _ => None,
+    }
+}
+
+fn embedded_neighbors(locale: SupportedLocale) -> Option<&'static str> {
+    match locale {
+        #[cfg(feature = "en-us")]
+        SupportedLocale::EnUs => Some(crate::support::en_us::DICT_PREPROC),
+        #[cfg(feature = "en-gb")]
+        SupportedLocale::EnGb => Some(crate::support::en_gb::DICT_PREPROC),
+        _ => None,
+    }
+}
+
+fn embedded_pinyin() -> Option<&'static str> {
+    #[cfg(feature = "zh-cn")]
+    return Some(crate::support::zh_cn::PINYIN);
+
+    #[cfg(not(feature = "zh-cn"))]
+    None
+}
+
 fn normalize_max_edit(max_edit: u8) -> u8 {
+    match check_max_edit(max_edit) {
+        Ok(max_edit) => max_edit,
+        Err(e) => {
+            eprintln!("{}", e);
+            max_edit.clamp(1, MAX_EDIT_THRESHOLD)
+        },
+    }
+}
+
+fn check_max_edit(max_edit: u8) -> Result<u8, Error> {
     if max_edit > MAX_EDIT_THRESHOLD {
-        eprintln!(
-            "Only support max edits less or equal to {}.",
-            MAX_EDIT_THRESHOLD
-        );
-        3
+        Err(Error::InvalidConfig(
+            format!("only support max edits less or equal to {}", MAX_EDIT_THRESHOLD)
+        ))
     } else if max_edit < 1 {
-        eprintln!("Only support max edits greater or equal to 1.");
-        1
+        Err(Error::InvalidConfig(String::from("only support max edits greater or equal to 1")))
     } else {
-        max_edit
+        Ok(max_edit)
     }
 }
@@ -0,0 +1,177 @@
+use std::io::BufRead;
+
+use hashbrown::HashMap;
+
+use common;
+use error::Error;
+use language::{LanguageModel, SENTENCE_END, SENTENCE_START};
+
+/// The sets of valid words commonly typed one for another, e.g. "their", "there" and "they're",
+/// which no edit distance can tell apart since each of them is a known word.
+#[derive(Debug, Clone, Default)]
+pub struct ConfusionSets {
+    sets: Vec<Vec<String>>,
+    members: HashMap<String, Vec<usize>>,
+}
+
+/// A known word that's likely the wrong one in its context.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RealWordError {
+    /// The byte offset of the word in the text.
+    pub offset: usize,
+    /// The word as it's written in the text.
+    pub word: String,
+    /// The word of the same confusion set that fits the context best.
+    pub suggestion: String,
+    /// The chance that the suggestion is the word meant, among the words of the set.
+    pub confidence: f64,
+}
+
+impl ConfusionSets {
+    pub fn new() -> Self {
+        ConfusionSets::default()
+    }
+
+    /// Load the sets from the file at `path`, one set per line, with its words separated by
+    /// commas, e.g. `their,there,they're`.
+    pub fn load(path: &str) -> Result<Self, Error> {
+        let reader = common::open_file(path)?;
+        let mut sets = ConfusionSets::new();
+
+        for (idx, line) in reader.lines().enumerate() {
+            let line = line.map_err(|err| Error::io(path, err))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let words: Vec<String> = line
+                .split(common::DELIM)
+                .map(|word| word.trim().to_lowercase())
+                .filter(|word| !word.is_empty())
+                .collect();
+
+            if words.len() < 2 {
+                return Err(Error::MalformedLine { line: idx + 1, content: line.to_owned() });
+            }
+
+            sets.add(words);
+        }
+
+        Ok(sets)
+    }
+
+    /// Add a set of words confused with one another.
+    pub fn add(&mut self, words: Vec<String>) {
+        let id = self.sets.len();
+
+        for word in words.iter() {
+            let sets = self.members.entry(word.to_owned()).or_insert_with(Vec::new);
+            if !sets.contains(&id) {
+                sets.push(id);
+            }
+        }
+
+        self.sets.push(words);
+    }
+
+    /// The words the `word` is confused with, itself excluded, from all the sets it belongs to.
+    pub fn alternatives(&self, word: &str) -> Vec<&str> {
+        let mut alternatives: Vec<&str> = Vec::new();
+
+        if let Some(sets) = self.members.get(word) {
+            for id in sets {
+                for other in self.sets[*id].iter() {
+                    if other != word && !alternatives.contains(&other.as_str()) {
+                        alternatives.push(other);
+                    }
+                }
+            }
+        }
+
+        alternatives
+    }
+
+    pub fn len(&self) -> usize {
+        self.sets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sets.is_empty()
+    }
+}
+
+/// Weigh each word of a confusion set in the place of the token, by the chance of the n-grams
+/// around it: the words before it, and the words after it that the `model` conditions on it.
+/// The token is flagged if any other word of the set fits better.
+pub(crate) fn detect(
+    tokens: &[(usize, &str)],
+    sets: &ConfusionSets,
+    model: &LanguageModel
+) -> Vec<RealWordError> {
+    let mut words: Vec<String> = Vec::with_capacity(tokens.len() + 2);
+    words.push(SENTENCE_START.to_owned());
+    words.extend(tokens.iter().map(|(_, token)| token.to_lowercase()));
+    words.push(SENTENCE_END.to_owned());
+
+    let mut errors = Vec::new();
+
+    for (pos, (offset, token)) in tokens.iter().enumerate().map(|(idx, token)| (idx + 1, token)) {
+        let alternatives = sets.alternatives(&words[pos]);
+        if alternatives.is_empty() {
+            continue;
+        }
+
+        let typed = context_score(&words, pos, &words[pos], model);
+        let scores: Vec<(&str, f64)> = alternatives
+            .into_iter()
+            .map(|word| (word, context_score(&words, pos, word, model)))
+            .collect();
+
+        let best = scores
+            .iter()
+            .fold(None, |best: Option<&(&str, f64)>, curr| match best {
+                Some(best) if best.1 >= curr.1 => Some(best),
+                _ => Some(curr),
+            });
+
+        if let Some((suggestion, score)) = best {
+            if *score <= typed {
+                continue;
+            }
+
+            // normalized over the set, with the scores relative to the best one to stay in range
+            let total: f64 = scores
+                .iter()
+                .map(|(_, other)| *other)
+                .chain(Some(typed))
+                .map(|other| 10f64.powf(other - score))
+                .sum();
+
+            errors.push(RealWordError {
+                offset: *offset,
+                word: (*token).to_owned(),
+                suggestion: (*suggestion).to_owned(),
+                confidence: 1.0 / total,
+            });
+        }
+    }
+
+    errors
+}
+
+/// The log10 chance of the n-grams covering the position `pos`, with the `word` in its place.
+fn context_score(words: &[String], pos: usize, word: &str, model: &LanguageModel) -> f64 {
+    let history = model.order().saturating_sub(1);
+    let last = (pos + history).min(words.len() - 1);
+
+    (pos..=last)
+        .map(|curr| {
+            let context: Vec<&str> = (curr.saturating_sub(history)..curr)
+                .map(|idx| if idx == pos { word } else { words[idx].as_str() })
+                .collect();
+
+            let target = if curr == pos { word } else { words[curr].as_str() };
+            model.log_prob(&context, target)
+        })
+        .sum()
+}
@@ -0,0 +1,98 @@
+use std::cmp::Ordering;
+
+use candidate::Candidate;
+
+// the chance of each edit, weighing the frequencies when the scorer gives no probability
+static EDIT_CHANCE: f64 = 0.01;
+
+/// What to do with a typed word.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decision {
+    /// The word is fine as it is, or there's nothing better to offer.
+    Keep,
+    /// The candidate is confidently the word meant, and can replace the input silently.
+    Replace(Candidate),
+    /// The best ranked candidates, none confidently enough to replace the input.
+    Suggest(Vec<Candidate>),
+}
+
+/// When `AutoCorrect::autocorrect` may replace the input without asking.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecisionPolicy {
+    /// The least lead of the top candidate's confidence over the runner-up's, between 0 and 1.
+    pub margin: f64,
+    /// The inputs shorter than this, in characters, are too ambiguous to replace.
+    pub min_length: usize,
+    /// The known inputs at least this frequent are always kept.
+    pub keep_frequency: u32,
+    /// The most candidates to suggest.
+    pub max_suggestions: usize,
+}
+
+impl Default for DecisionPolicy {
+    fn default() -> Self {
+        DecisionPolicy {
+            margin: 0.5,
+            min_length: 3,
+            keep_frequency: 10_000,
+            max_suggestions: 3,
+        }
+    }
+}
+
+impl DecisionPolicy {
+    /// Decide from the ranked `candidates` of the `input`, and its dictionary frequency.
+    pub(crate) fn decide(
+        &self,
+        input: &str,
+        frequency: Option<u32>,
+        mut candidates: Vec<Candidate>
+    ) -> Decision {
+        if frequency.is_some_and(|score| score >= self.keep_frequency) {
+            return Decision::Keep;
+        }
+
+        // a known input competes with its corrections, by its own frequency
+        let confidences = confidences(&candidates);
+        let mut order: Vec<usize> = (0..candidates.len()).collect();
+        order.sort_by(|a, b| {
+            confidences[*b].partial_cmp(&confidences[*a]).unwrap_or(Ordering::Equal)
+        });
+
+        let top = match order.first() {
+            Some(top) if candidates[*top].word.to_lowercase() != input => *top,
+            _ => return Decision::Keep,
+        };
+
+        let lead = confidences[top] - order.get(1).map_or(0.0, |second| confidences[*second]);
+        if input.chars().count() >= self.min_length && lead >= self.margin {
+            return Decision::Replace(candidates.swap_remove(top));
+        }
+
+        candidates.retain(|candidate| candidate.word.to_lowercase() != input);
+        candidates.truncate(self.max_suggestions.max(1));
+        Decision::Suggest(candidates)
+    }
+}
+
+/// The chance of each candidate being the word meant: the probabilities from the scorer if it
+/// gives them, or else the frequencies discounted by the edits, normalized over the candidates.
+fn confidences(candidates: &[Candidate]) -> Vec<f64> {
+    let weights: Vec<f64> = if candidates.iter().all(|candidate| candidate.probability.is_some()) {
+        candidates.iter().map(|candidate| candidate.probability.unwrap_or(0.0)).collect()
+    } else {
+        candidates
+            .iter()
+            .map(|candidate| {
+                (f64::from(candidate.score) + 1.0) * EDIT_CHANCE.powi(i32::from(candidate.edit))
+            })
+            .collect()
+    };
+
+    let total: f64 = weights.iter().sum();
+    if total <= 0.0 {
+        return vec![0.0; candidates.len()];
+    }
+
+    weights.into_iter().map(|weight| weight / total).collect()
+}
@@ -1,63 +1,91 @@
-use crate::AutoCorrect;
+use std::sync::Arc;
+
+use threads_pool::ThreadPool;
+
+use crate::{AutoCorrect, Index};
 use crate::crossbeam_channel as channel;
 use crate::candidate::Candidate;
 use crate::common;
 use crate::config::Config;
-use crate::config::SupportedLocale;
-use crate::trie::Node;
+use crate::config::{RunMode, SupportedLocale};
+use crate::error::Error;
+use crate::phonetic::PhoneticIndex;
+use crate::pinyin::PinyinIndex;
+use crate::stores::Store;
+use crate::trie::Trie;
+
+/// Everything a single query needs: the handles shared with the service that launched it.
+/// Cloning the context is cheap.
+#[derive(Clone)]
+pub(crate) struct Context {
+    pub(crate) dict: Arc<Trie>,
+    pub(crate) index: Arc<Index>,
+    pub(crate) max_edit: u8,
+    pub(crate) locale: SupportedLocale,
+    pub(crate) run_mode: RunMode,
+    pub(crate) phonetic: Option<Arc<PhoneticIndex>>,
+    pub(crate) pinyin: Option<Arc<PinyinIndex>>,
+    pub(crate) split_words: bool,
+}
 
-pub(crate) fn initialize(service: &AutoCorrect) {
-    if let Err(e) = populate_words_set(&service.config) {
-        eprintln!("Failed to initialize: {}", e);
-    }
+pub(crate) fn initialize(config: &Config, pool: &ThreadPool) -> Result<Trie, Error> {
+    populate_words_set(config, pool)
 }
 
+/// Walk the dictionary trie for the words within `max_edit` from `word`. Every word is reached
+/// once, through the path that spells it, with its true minimal edit distance.
 pub(crate) fn candidate(
+    word: String,
+    ctx: &Context,
+    tx_async: &channel::Sender<Candidate>
+) {
+    let word = word.trim().to_lowercase();
+    if word.is_empty() {
+        return;
+    }
+
+    ctx.dict.walk(&word, ctx.max_edit, |found, score, edit| {
+        tx_async.send(Candidate::new(found.to_owned(), score, edit)).is_ok()
+    });
+}
+
+/// Fan out the variations of `word` one edit at a time from the calling thread, and send the
+/// ones that are known words. This is the search the trie walk replaced, kept for the benches
+/// to measure the two against each other: the inserts and replaces, and the deletes and
+/// transposes, of each variation are generated by two jobs on the `pool`, and every variation,
+/// misspelled or not, is searched again at the next edit, deduped by the query's `store`. Only
+/// the lowercase ASCII words are searched, as the variations are sliced by bytes.
+pub(crate) fn fan_out(
     word: String,
     edit: u8,
-    max_edit: u8,
-    locale: SupportedLocale,
+    ctx: &Context,
+    pool: &Arc<ThreadPool>,
+    store: &Arc<Store>,
     tx_async: &channel::Sender<Candidate>,
     marker: u32
 ) {
-    if edit >= max_edit {
+    if edit >= ctx.max_edit {
         return;
     }
 
     let word = word.trim().to_lowercase();
-    if word.is_empty() {
+    if word.is_empty() || !word.is_ascii() {
         return;
     }
 
-    if let Some(score) = Node::check(&word) {
+    if let Some(score) = ctx.dict.check(&word) {
         if tx_async.send(Candidate::new(word.to_owned(), score, edit)).is_err() {
             return;
         }
     }
 
-/*
-    if let Some(set) = dict_ref() {
-        if set.contains_key(&word) {
-            let candidate = Candidate::new(word.to_owned(), set[&word], edit);
-
-            if let Some(tx) = tx_async {
-                if let Err(_) = tx.send(candidate) {
-                    return HashSet::new();
-                }
-            } else {
-                results.insert(candidate);
-            }
-        }
-    }
-*/
-
     // if a misspell, find the correct one within 1 edit distance
-    let (tx_curr, rx_curr) = channel::bounded(64);
+    let (tx_curr, rx_curr) = channel::unbounded();
     let current_edit = edit + 1;
 
     let (tx_next, tx_next_clone, rx_next) =
-        if current_edit < max_edit {
-            let (tx_raw, rx_raw) = channel::bounded(256);
+        if current_edit < ctx.max_edit {
+            let (tx_raw, rx_raw) = channel::unbounded();
             let tx_raw_clone = tx_raw.clone();
             (Some(tx_raw), Some(tx_raw_clone), Some(rx_raw))
         } else {
@@ -66,58 +94,47 @@ pub(crate) fn candidate(
 
     let word_clone = word.clone();
     let tx_clone = tx_curr.clone();
+    let dict = Arc::clone(&ctx.dict);
+    let job_store = Arc::clone(store);
 
-    AutoCorrect::run_job(move || {
+    let ins_job = AutoCorrect::run_job(pool, move || {
         common::ins_repl(
+            &dict,
+            &job_store,
             &word_clone,
             current_edit,
             tx_clone,
             tx_next_clone,
             marker
         );
-
-/*
-            common::deprecated::delete_n_replace(
-                word_clone,
-                set,
-                current_edit,
-                tx_clone,
-                tx_next_clone
-            )
-        }
-*/
     });
 
-    if let Some(chan) = rx_next {
-        let tx = tx_async.clone();
-        AutoCorrect::run_job(move || {
-            find_next_edit_candidates(
-                current_edit, max_edit, locale, chan, &tx
-            );
-        });
-    }
+    let dict = Arc::clone(&ctx.dict);
+    let job_store = Arc::clone(store);
 
-    AutoCorrect::run_job(move || {
+    let del_job = AutoCorrect::run_job(pool, move || {
         common::del_tran(
+            &dict,
+            &job_store,
             &word,
             current_edit,
             tx_curr,
             tx_next,
             marker
         );
+    });
+
+    // a job that can't be scheduled drops its channels, so the search carries on with what the
+    // other jobs have found
+    if let Err(e) = ins_job.and(del_job) {
+        eprintln!("{}", e);
+    }
 
-/*
-        if let Some(set) = dict_ref() {
-            common::deprecated::transpose_n_insert(
-                word,
-                set,
-                current_edit,
-                tx,
-                tx_next
-            );
+    if let Some(chan) = rx_next {
+        for (next, marker) in chan {
+            fan_out(next, current_edit, ctx, pool, store, tx_async, marker);
         }
-*/
-    });
+    }
 
     // move rx into the scope so it can drop afterwards
     for candidate in rx_curr {
@@ -127,34 +144,15 @@ pub(crate) fn candidate(
     }
 }
 
-fn find_next_edit_candidates(
-    edit: u8,
-    max_edit: u8,
-    locale: SupportedLocale,
-    rx_next: channel::Receiver<(String, u32)>,
-    tx_async: &channel::Sender<Candidate>
-) {
-    for (next, marker) in rx_next {
-        candidate(
-            next,
-            edit,
-            max_edit,
-            locale,
-            tx_async,
-            marker
-        );
-    }
-}
-
-fn populate_words_set(config: &Config) -> Result<(), String> {
+fn populate_words_set(config: &Config, pool: &ThreadPool) -> Result<Trie, Error> {
     let (tx, rx) = channel::unbounded();
-    let dict_path = config.get_dict_path();
-
-    AutoCorrect::run_job(move || {
-        common::load_dict_async(dict_path, tx);
-    });
+    let source = config.get_dict_source()?;
+    let path = source.describe("(embedded dictionary)");
+    let reader = common::open_dict(source)?;
 
-    Node::build(rx);
+    AutoCorrect::run_job(pool, move || {
+        common::load_dict_async(reader, path, tx);
+    })?;
 
-    Ok(())
+    Trie::build(rx)
 }
@@ -0,0 +1,50 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+/// Errors raised while setting up or running the auto-correct service.
+#[derive(Debug)]
+pub enum Error {
+    /// The dictionary file can't be found at the given path.
+    MissingDictionary(String),
+    /// Any other file the service loads, e.g. the language model or the confusion sets, can't
+    /// be found at the given path.
+    MissingFile(String),
+    /// A line of the dictionary, or of any other file the service loads, can't be parsed;
+    /// `line` is 1-based.
+    MalformedLine { line: usize, content: String },
+    /// The file at `path` is there, but can't be opened, read or written, e.g. for a line that
+    /// isn't valid UTF-8.
+    Io { path: String, reason: String },
+    /// The configuration carries a value out of the supported range.
+    InvalidConfig(String),
+    /// The worker pool failed to take the job.
+    Executor(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::MissingDictionary(path) => {
+                write!(f, "Unable to open the dictionary at path: {}", path)
+            },
+            Error::MissingFile(path) => write!(f, "Unable to open the file at path: {}", path),
+            Error::MalformedLine { line, content } => {
+                write!(f, "Malformed content at line {}: {:?}", line, content)
+            },
+            Error::Io { path, reason } => {
+                write!(f, "Failed to access the file at path {}: {}", path, reason)
+            },
+            Error::InvalidConfig(reason) => write!(f, "Invalid configuration: {}", reason),
+            Error::Executor(reason) => write!(f, "Failed to execute the job: {}", reason),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl Error {
+    pub(crate) fn io(path: &str, err: io::Error) -> Self {
+        Error::Io { path: path.to_owned(), reason: err.to_string() }
+    }
+}
@@ -1,30 +1,229 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crossbeam_channel as channel;
+use hashbrown::{HashMap, HashSet};
+use threads_pool::ThreadPool;
+
 use super::AutoCorrect;
-use std::sync::RwLock;
+use candidate::Candidate;
+use common;
+use config::{AutoCorrectConfig, Config, SupportedLocale};
+use error::Error;
+use trie::Trie;
 
-use config::{Config};
-use hashbrown::HashMap;
+// The first line of a versioned neighbor index, as generated by the build script. Indexes
+// without a header are the legacy ones, which share the same format.
+static NEIGHBORS_HEADER: &str = "#auto_correct:neighbors:v1";
+static NEIGHBOR_DELIM: char = '^';
+static LIST_DELIM: char = ';';
 
-lazy_static! {
-    static ref WORDS_SET: RwLock<Box<HashMap<String, u32>>> = RwLock::new(Box::new(HashMap::new()));
-    static ref REVERSE_DICT: RwLock<Box<HashMap<String, Vec<String>>>> = RwLock::new(Box::new(HashMap::new()));
+/// The reverse index for the `SpeedSensitive` mode: every dictionary word, mapped to the other
+/// dictionary words within 1 edit distance from it. Since the edit distance is symmetric, deeper
+/// edits are composed by walking the index from the words found at the previous layer.
+#[derive(Debug, Default)]
+pub(crate) struct ReverseDict {
+    neighbors: HashMap<String, Vec<String>>,
 }
 
-pub(crate) fn initialize(service: &AutoCorrect) {
-    // if already initialized, calling this function takes no effect
-    if let Err(e) = populate_words_set(&service.config) {
-        eprintln!("Failed to initialize: {}", e);
-        return;
+impl ReverseDict {
+    /// Parse the precomputed index, where each line is formatted as `word^n1;n2;...;`.
+    pub(crate) fn build(
+        rx: channel::Receiver<Result<(usize, String), Error>>
+    ) -> Result<Self, Error> {
+        let mut neighbors = HashMap::new();
+
+        for received in rx {
+            let (line, received) = received?;
+            if received.trim().is_empty() {
+                continue;
+            }
+
+            if received.starts_with('#') {
+                // only the header of a supported version is allowed
+                if line != 1 || received.trim() != NEIGHBORS_HEADER {
+                    return Err(Error::MalformedLine { line, content: received.to_owned() });
+                }
+
+                continue;
+            }
+
+            let temp: Vec<&str> = received.trim().splitn(2, NEIGHBOR_DELIM).collect();
+            if temp.len() < 2 || temp[0].is_empty() {
+                return Err(Error::MalformedLine { line, content: received.to_owned() });
+            }
+
+            let list: Vec<String> = temp[1]
+                .split(LIST_DELIM)
+                .filter(|neighbor| !neighbor.is_empty())
+                .map(|neighbor| neighbor.to_owned())
+                .collect();
+
+            neighbors.insert(temp[0].to_owned(), list);
+        }
+
+        Ok(ReverseDict { neighbors })
     }
-}
 
-pub(crate) fn set_reverse_dict(dict: HashMap<String, Vec<String>>) {
-    if let Ok(mut rev_dict) = REVERSE_DICT.write() {
-        *rev_dict = Box::new(dict);
+    /// Compute the index from the words in the dictionary, checking each of their 1-edit
+    /// variations against the dictionary.
+    pub(crate) fn generate(dict: &Trie, locale: SupportedLocale) -> Self {
+        let mut neighbors = HashMap::new();
+
+        for (word, _) in dict.words() {
+            let mut list: Vec<String> = Vec::new();
+
+            for variation in common::edits1(&word, locale) {
+                if dict.check(&variation).is_some() && !list.contains(&variation) {
+                    list.push(variation);
+                }
+            }
+
+            if !list.is_empty() {
+                neighbors.insert(word, list);
+            }
+        }
+
+        ReverseDict { neighbors }
+    }
+
+    pub(crate) fn get(&self, word: &str) -> Option<&Vec<String>> {
+        self.neighbors.get(word)
+    }
+
+    /// Save the index to the `path`, in the same format that `build` parses.
+    pub(crate) fn save(&self, path: &str) -> Result<(), Error> {
+        let file = File::create(path).map_err(|err| Error::io(path, err))?;
+        let mut writer = BufWriter::new(file);
+
+        writer
+            .write_all(format!("{}\n", NEIGHBORS_HEADER).as_bytes())
+            .map_err(|err| Error::io(path, err))?;
+
+        for (word, list) in self.neighbors.iter() {
+            let mut line = String::with_capacity(word.len() * (list.len() + 1) * 2);
+
+            line.push_str(word);
+            line.push(NEIGHBOR_DELIM);
+
+            for neighbor in list {
+                line.push_str(neighbor);
+                line.push(LIST_DELIM);
+            }
+
+            line.push('\n');
+
+            writer
+                .write_all(line.as_bytes())
+                .map_err(|err| Error::io(path, err))?;
+        }
+
+        writer.flush().map_err(|err| Error::io(path, err))
     }
 }
 
-fn populate_words_set(_config: &Config) -> Result<(), String> {
-    Ok(())
+pub(crate) fn initialize(
+    config: &Config,
+    pool: &ThreadPool,
+    dict: &Trie
+) -> Result<ReverseDict, Error> {
+    match config.get_neighbors_source() {
+        Some(source) => {
+            let (tx, rx) = channel::unbounded();
+            let path = source.describe("(embedded neighbors)");
+            let reader = common::open_dict(source)?;
+
+            AutoCorrect::run_job(pool, move || {
+                common::load_dict_async(reader, path, tx);
+            })?;
+
+            ReverseDict::build(rx)
+        },
+        None => Ok(ReverseDict::generate(dict, config.get_locale())),
+    }
 }
 
-//TODO: only need table for 1 edit-distance, all ensuing distance can be stacked upon the previous layer
\ No newline at end of file
+/// Find the candidates from the precomputed neighborhoods: the input itself if it's a known
+/// word, otherwise its 1-edit variations that are known words, then expand the found words
+/// layer by layer through the reverse index until reaching the `max_edit`.
+///
+/// Note that the reverse index only links known words, so for a misspelled input, the words
+/// that can only be reached through other misspelled words at deeper edits are not found. For
+/// the same reason, the layer a word is reached at can exceed its distance from the input, so
+/// the edit of each word found beyond the first layer is measured against the input.
+pub(crate) fn candidate(
+    word: String,
+    max_edit: u8,
+    locale: SupportedLocale,
+    dict: &Trie,
+    reverse_dict: &ReverseDict,
+    tx_async: &channel::Sender<Candidate>
+) {
+    let word = word.trim().to_lowercase();
+    if word.is_empty() || max_edit == 0 {
+        return;
+    }
+
+    let mut visited: HashSet<String> = HashSet::with_capacity(64);
+    let mut frontier: Vec<String> = Vec::new();
+    let mut edit = 0;
+
+    if let Some(score) = dict.check(&word) {
+        if tx_async.send(Candidate::new(word.to_owned(), score, 0)).is_err() {
+            return;
+        }
+
+        frontier.push(word.to_owned());
+    } else {
+        edit = 1;
+
+        for variation in common::edits1(&word, locale) {
+            if visited.contains(&variation) {
+                continue;
+            }
+
+            if let Some(score) = dict.check(&variation) {
+                visited.insert(variation.clone());
+
+                if tx_async.send(Candidate::new(variation.clone(), score, 1)).is_err() {
+                    return;
+                }
+
+                frontier.push(variation);
+            }
+        }
+    }
+
+    visited.insert(word.to_owned());
+
+    while edit < max_edit && !frontier.is_empty() {
+        edit += 1;
+
+        let mut next = Vec::new();
+        for found in frontier.iter() {
+            let neighbors = match reverse_dict.get(found) {
+                Some(neighbors) => neighbors,
+                None => continue,
+            };
+
+            for neighbor in neighbors {
+                if visited.contains(neighbor) {
+                    continue;
+                }
+
+                visited.insert(neighbor.clone());
+
+                if let Some(score) = dict.check(neighbor) {
+                    let distance = common::damerau_distance(&word, neighbor) as u8;
+                    if tx_async.send(Candidate::new(neighbor.clone(), score, distance)).is_err() {
+                        return;
+                    }
+
+                    next.push(neighbor.clone());
+                }
+            }
+        }
+
+        frontier = next;
+    }
+}
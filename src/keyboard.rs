@@ -0,0 +1,69 @@
+use hashbrown::HashMap;
+
+static QWERTY: [&str; 3] = ["qwertyuiop", "asdfghjkl", "zxcvbnm"];
+static AZERTY: [&str; 3] = ["azertyuiop", "qsdfghjklm", " wxcvbn"];
+static QWERTZ: [&str; 3] = ["qwertzuiopü", "asdfghjklöä", " yxcvbnm"];
+static DVORAK: [&str; 3] = ["',.pyfgcrl", "aoeuidhtns", ";qjkxbmwvz"];
+
+/// The positions of the keys on a keyboard layout, to tell which keys are next to each other.
+#[derive(Debug, Clone)]
+pub struct Keyboard {
+    keys: HashMap<char, (usize, usize)>,
+}
+
+impl Keyboard {
+    pub fn qwerty() -> Self {
+        Keyboard::from_rows(&QWERTY)
+    }
+
+    pub fn azerty() -> Self {
+        Keyboard::from_rows(&AZERTY)
+    }
+
+    pub fn qwertz() -> Self {
+        Keyboard::from_rows(&QWERTZ)
+    }
+
+    pub fn dvorak() -> Self {
+        Keyboard::from_rows(&DVORAK)
+    }
+
+    /// A user defined layout, from the rows of keys top to bottom, where each row is shifted
+    /// right from the one above by less than a key, like on a regular keyboard. A space leaves
+    /// a gap in the row.
+    pub fn from_rows(rows: &[&str]) -> Self {
+        let mut keys = HashMap::new();
+
+        for (row, line) in rows.iter().enumerate() {
+            for (col, key) in line.chars().enumerate() {
+                if key != ' ' {
+                    keys.insert(key, (row, col));
+                }
+            }
+        }
+
+        Keyboard { keys }
+    }
+
+    /// Check if the 2 keys touch each other: either next to each other on the same row, or on
+    /// adjacent rows, where a key touches the key right above it and the one after.
+    pub fn is_near(&self, a: char, b: char) -> bool {
+        let (upper, lower) = match (self.keys.get(&a), self.keys.get(&b)) {
+            (Some(a), Some(b)) if a.0 <= b.0 => (*a, *b),
+            (Some(a), Some(b)) => (*b, *a),
+            _ => return false,
+        };
+
+        if upper.0 == lower.0 {
+            return upper.1 + 1 == lower.1 || lower.1 + 1 == upper.1;
+        }
+
+        upper.0 + 1 == lower.0 && (upper.1 == lower.1 || upper.1 == lower.1 + 1)
+    }
+}
+
+impl Default for Keyboard {
+    fn default() -> Self {
+        Keyboard::qwerty()
+    }
+}
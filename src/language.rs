@@ -0,0 +1,225 @@
+use std::io::BufRead;
+
+use hashbrown::HashMap;
+
+use common;
+use error::Error;
+
+// the log10 weight of backing off to a shorter history in the count based models
+static BACKOFF: f64 = -0.397_940_008_672_037_6;
+
+// the log10 chance of a word unknown to an ARPA model without an `<unk>` entry
+static UNKNOWN: f64 = -7.0;
+
+/// The marker before the first word of a sentence.
+pub(crate) static SENTENCE_START: &str = "<s>";
+
+/// The marker after the last word of a sentence.
+pub(crate) static SENTENCE_END: &str = "</s>";
+
+/// An n-gram language model, giving the log10 chance of a word after the words before it.
+///
+/// It's loaded either from an ARPA file, with the backoff weights it defines, or from a list of
+/// n-gram counts formatted as `w1 w2,count`, scored with the stupid backoff: the relative
+/// frequency of the n-gram after its history, or 0.4 times the score after the shorter history.
+/// The unigrams of a count list are add-one smoothed.
+#[derive(Debug, Clone)]
+pub struct LanguageModel {
+    order: usize,
+    ngrams: HashMap<String, Entry>,
+    backoff: f64,
+    unknown: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    log_prob: f64,
+    backoff: Option<f64>,
+}
+
+impl LanguageModel {
+    /// Load the model from the file at `path`, in the ARPA format if it starts with the `\data\`
+    /// header, or as a list of `w1 w2,count` lines otherwise.
+    pub fn load(path: &str) -> Result<Self, Error> {
+        let reader = common::open_file(path)?;
+        let mut lines = Vec::new();
+
+        for line in reader.lines() {
+            lines.push(line.map_err(|err| Error::io(path, err))?);
+        }
+
+        let arpa = lines
+            .iter()
+            .find(|line| !line.trim().is_empty())
+            .is_some_and(|line| line.trim() == "\\data\\");
+
+        if arpa {
+            LanguageModel::parse_arpa(&lines)
+        } else {
+            LanguageModel::parse_counts(&lines)
+        }
+    }
+
+    /// Build the model from the n-gram counts, each n-gram being its words separated by spaces.
+    pub fn from_counts<I>(counts: I) -> Self
+        where I: IntoIterator<Item = (String, u64)>
+    {
+        let mut ngrams: HashMap<String, u64> = HashMap::new();
+        let mut order = 1;
+
+        for (ngram, count) in counts {
+            let words: Vec<&str> = ngram.split_whitespace().collect();
+            if words.is_empty() {
+                continue;
+            }
+
+            order = order.max(words.len());
+            *ngrams.entry(words.join(" ")).or_insert(0) += count;
+        }
+
+        // without any unigram, the words count as often as the n-grams they end
+        let mut unigrams: HashMap<String, u64> = HashMap::new();
+        let mut histories: HashMap<String, u64> = HashMap::new();
+        let explicit = ngrams.keys().any(|ngram| !ngram.contains(' '));
+
+        for (ngram, count) in ngrams.iter() {
+            match ngram.rfind(' ') {
+                Some(pos) => {
+                    *histories.entry(ngram[..pos].to_owned()).or_insert(0) += count;
+
+                    if !explicit {
+                        *unigrams.entry(ngram[pos + 1..].to_owned()).or_insert(0) += count;
+                    }
+                },
+                None => {
+                    unigrams.insert(ngram.to_owned(), *count);
+                },
+            }
+        }
+
+        let total = (unigrams.values().sum::<u64>() + unigrams.len() as u64).max(1) as f64;
+        let mut entries: HashMap<String, Entry> = unigrams
+            .into_iter()
+            .map(|(word, count)| {
+                (word, Entry { log_prob: ((count + 1) as f64 / total).log10(), backoff: None })
+            })
+            .collect();
+
+        for (ngram, count) in ngrams.into_iter() {
+            let pos = match ngram.rfind(' ') {
+                Some(pos) => pos,
+                None => continue,
+            };
+
+            let history = histories.get(&ngram[..pos]).cloned().unwrap_or(count).max(1);
+            let log_prob = (count as f64 / history as f64).log10();
+
+            entries.insert(ngram, Entry { log_prob, backoff: None });
+        }
+
+        LanguageModel { order, ngrams: entries, backoff: BACKOFF, unknown: (1.0 / total).log10() }
+    }
+
+    /// The longest n-gram of the model.
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    /// The log10 chance of the `word` following the `history`, of which only the last
+    /// `order - 1` words count.
+    pub fn log_prob(&self, history: &[&str], word: &str) -> f64 {
+        let start = history.len().saturating_sub(self.order - 1);
+        let mut penalty = 0.0;
+
+        for from in start..=history.len() {
+            let context = &history[from..];
+
+            let mut key = context.join(" ");
+            if !key.is_empty() {
+                key.push(' ');
+            }
+            key.push_str(word);
+
+            if let Some(entry) = self.ngrams.get(&key) {
+                return penalty + entry.log_prob;
+            }
+
+            if !context.is_empty() {
+                penalty += self.ngrams
+                    .get(&context.join(" "))
+                    .and_then(|entry| entry.backoff)
+                    .unwrap_or(self.backoff);
+            }
+        }
+
+        penalty + self.unknown
+    }
+
+    fn parse_arpa(lines: &[String]) -> Result<Self, Error> {
+        let mut ngrams = HashMap::new();
+        let mut order = 1;
+        let mut section = 0;
+
+        for (idx, line) in lines.iter().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed == "\\data\\" || trimmed.starts_with("ngram ") {
+                continue;
+            }
+
+            if trimmed == "\\end\\" {
+                break;
+            }
+
+            if trimmed.starts_with('\\') && trimmed.ends_with("-grams:") {
+                section = trimmed[1..trimmed.len() - 7]
+                    .parse()
+                    .map_err(|_| Error::MalformedLine { line: idx + 1, content: line.to_owned() })?;
+
+                order = order.max(section);
+                continue;
+            }
+
+            // the chance, the words, and an optional backoff weight
+            let fields: Vec<&str> = trimmed.split_whitespace().collect();
+            let log_prob = fields.first().and_then(|field| field.parse::<f64>().ok());
+
+            match log_prob {
+                Some(log_prob) if section > 0 && fields.len() > section => {
+                    let backoff = match fields.get(section + 1) {
+                        Some(field) => Some(field.parse::<f64>().map_err(|_| {
+                            Error::MalformedLine { line: idx + 1, content: line.to_owned() }
+                        })?),
+                        None => None,
+                    };
+
+                    ngrams.insert(fields[1..=section].join(" "), Entry { log_prob, backoff });
+                },
+                _ => return Err(Error::MalformedLine { line: idx + 1, content: line.to_owned() }),
+            }
+        }
+
+        let unknown = ngrams.get("<unk>").map_or(UNKNOWN, |entry: &Entry| entry.log_prob);
+        Ok(LanguageModel { order, ngrams, backoff: 0.0, unknown })
+    }
+
+    fn parse_counts(lines: &[String]) -> Result<Self, Error> {
+        let mut counts = Vec::new();
+
+        for (idx, line) in lines.iter().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let mut parts = line.rsplitn(2, common::DELIM);
+            let count = parts.next().and_then(|count| count.trim().parse::<u64>().ok());
+            let ngram = parts.next().map(|ngram| ngram.trim().to_lowercase());
+
+            match (ngram, count) {
+                (Some(ngram), Some(count)) if !ngram.is_empty() => counts.push((ngram, count)),
+                _ => return Err(Error::MalformedLine { line: idx + 1, content: line.to_owned() }),
+            }
+        }
+
+        Ok(LanguageModel::from_counts(counts))
+    }
+}
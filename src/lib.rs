@@ -1,45 +1,105 @@
 #![allow(dead_code)]
 
-//! This library provides auto-correct suggestions that are within 1 edit distance from
-//! known English words.
+//! This library provides auto-correct suggestions for the words within the configured
+//! `max_edit` distance (1 to 3) from the known words of a locale: English (US or GB), with the
+//! bundled dictionaries, German, French, Spanish and Italian, with a supplied one, and
+//! Simplified Chinese, corrected by pinyin.
 
-#[macro_use]
-extern crate lazy_static;
 extern crate crossbeam_channel;
 extern crate threads_pool;
 extern crate hashbrown;
 extern crate proc_macro;
 
+mod automaton;
+mod bktree;
 mod candidate;
+mod case;
 mod common;
 mod config;
+mod confusion;
+mod decision;
 mod dynamic;
+mod error;
 mod hybrid;
+mod keyboard;
+mod language;
+mod metric;
+mod noisy;
+mod operation;
+mod phonetic;
+mod pinyin;
+mod scorer;
+mod sentence;
+mod split;
 mod stores;
 mod support;
+mod symspell;
+mod text;
 mod trie;
 
 pub mod prelude {
-    pub use candidate::Candidate;
-    pub use config::{AutoCorrectConfig, Config, SupportedLocale};
-    pub use AutoCorrect;
+    pub use bktree::BkTree;
+    pub use candidate::{Candidate, MatchKind};
+    pub use config::{AutoCorrectConfig, Config, RunMode, SupportedLocale};
+    pub use confusion::{ConfusionSets, RealWordError};
+    pub use decision::{Decision, DecisionPolicy};
+    pub use error::Error;
+    pub use keyboard::Keyboard;
+    pub use language::LanguageModel;
+    pub use metric::{Damerau, KeyboardWeighted, Levenshtein, Metric};
+    pub use noisy::{ErrorModel, NoisyChannel};
+    pub use operation::Operation;
+    pub use phonetic::Phonetic;
+    pub use scorer::{EditsThenFrequency, KeyboardScorer, Scorer};
+    pub use text::{apply, Fix, Issue};
+    pub use {AutoCorrect, ServiceUtils};
 }
 
-use std::sync::mpsc;
+pub use error::Error;
+
+use std::cmp::Ordering;
+use std::sync::{mpsc, Arc};
+use bktree::BkTree;
 use candidate::Candidate;
 use config::{AutoCorrectConfig, Config, RunMode, SupportedLocale};
+use confusion::{ConfusionSets, RealWordError};
+use decision::{Decision, DecisionPolicy};
+use dynamic::Context;
+use language::LanguageModel;
+use phonetic::{Phonetic, PhoneticIndex};
+use pinyin::PinyinIndex;
+use scorer::Scorer;
+use stores::Store;
+use hybrid::ReverseDict;
+use symspell::DeleteIndex;
+use text::Issue;
+use trie::Trie;
 
 use crossbeam_channel as channel;
-use hashbrown::HashSet;
-use threads_pool::ThreadPool;
-
-//TODO: define config struct -- 1. memory mode vs. speed mode;
-//TODO: customizable score function
-
-static mut POOL: Option<ThreadPool> = None;
+use hashbrown::{HashMap, HashSet};
+use threads_pool::{ExecutionError, ThreadPool};
 
+/// The auto-correct service. Each service owns its dictionary and its worker pool, so services
+/// built from different configurations can live side by side in the same process. Every query
+/// carries its own search state, so a single service can be shared across threads, e.g. behind
+/// an `Arc`.
 pub struct AutoCorrect {
     config: Config,
+    pool: Arc<ThreadPool>,
+    dict: Arc<Trie>,
+    index: Arc<Index>,
+    phonetic: Option<Arc<PhoneticIndex>>,
+    pinyin: Option<Arc<PinyinIndex>>,
+    language: Option<Arc<LanguageModel>>,
+    confusion: Option<Arc<ConfusionSets>>,
+}
+
+/// The run mode specific index, built on top of the dictionary trie when it's loaded.
+pub(crate) enum Index {
+    Empty,
+    Neighbors(ReverseDict),
+    Deletes(DeleteIndex),
+    BkTree(BkTree),
 }
 
 impl AutoCorrect {
@@ -48,88 +108,502 @@ impl AutoCorrect {
         AutoCorrect::new_with_config(Config::new())
     }
 
-    pub fn new_with_config(config: Config) -> AutoCorrect {
-        let service = AutoCorrect {
-            config,
-        };
+    /// Create the service with the given config. Failures are logged and leave the service
+    /// with an empty dictionary, and a `max_edit` out of range is brought back into it; use
+    /// `try_new_with_config` to handle them instead.
+    pub fn new_with_config(mut config: Config) -> AutoCorrect {
+        config.normalize();
+
+        let mut service = AutoCorrect::with_empty_dict(config);
 
-        AutoCorrect::pool_init(service.config.get_pool_size());
-        service.init_dict();
+        if let Err(e) = service.init_dict() {
+            eprintln!("Failed to initialize the service: {}", e);
+        }
 
         service
     }
 
+    /// Create the service with the given config, and fail fast if the config is invalid or the
+    /// dictionary can't be loaded.
+    pub fn try_new_with_config(config: Config) -> Result<AutoCorrect, Error> {
+        config.validate()?;
+
+        let mut service = AutoCorrect::with_empty_dict(config);
+        service.init_dict()?;
+
+        Ok(service)
+    }
+
+    fn with_empty_dict(config: Config) -> AutoCorrect {
+        let pool = ThreadPool::new(config.get_pool_size());
+
+        AutoCorrect {
+            config,
+            pool: Arc::new(pool),
+            dict: Arc::new(Trie::new()),
+            index: Arc::new(Index::Empty),
+            phonetic: None,
+            pinyin: None,
+            language: None,
+            confusion: None,
+        }
+    }
+
+    /// All the candidates within the max edits from the `word`, plus the phonetic matches if a
+    /// `Phonetic` algorithm is configured, ranked by the configured `Scorer`.
     pub fn candidates(&self, word: String) -> Vec<Candidate> {
-        let max_edit = self.config.get_max_edit();
-        let locale = self.config.get_locale();
+        let found = AutoCorrect::collect(word.clone(), &self.context());
+        self.rank(&word, found)
+    }
+
+    /// The `k` best ranked candidates, same as the first `k` of `candidates`. If the configured
+    /// `Scorer` ranks the fewer edits first, as the default one does, the search goes one edit
+    /// deeper at a time, and stops as soon as it has `k` candidates, since none of the words
+    /// further away could outrank them.
+    pub fn candidates_top_k(&self, word: String, k: usize) -> Vec<Candidate> {
+        if k == 0 {
+            return Vec::new();
+        }
 
-        stores::get_ready();
+        if !self.config.get_scorer().ranks_edits_first() {
+            let mut ranked = self.candidates(word);
+            ranked.truncate(k);
+            return ranked;
+        }
 
-        let (tx, rx) = channel::bounded(256);
-        AutoCorrect::run_job(move || {
-            dynamic::candidate(word, 0, max_edit, locale, &tx, 0);
-        });
+        let input = word.trim().to_lowercase();
+        let mut ctx = self.context();
+        let max_edit = ctx.max_edit;
 
-        let mut vec = Vec::with_capacity(256);
-        for candidate in rx {
-            if !vec.contains(&candidate) {
-                vec.push(candidate);
+        // a known word is its own best candidate
+        if k == 1 {
+            if let Some(score) = self.dict.check(&input) {
+                return self.rank(&word, vec![Candidate::new(input.to_owned(), score, 0)]);
+            }
+        }
+
+        for depth in 1..=max_edit {
+            ctx.max_edit = depth;
+
+            // the phonetic matches missed by the edits are all further than `max_edit` away
+            if depth < max_edit {
+                ctx.phonetic = None;
+            } else {
+                ctx.phonetic = self.phonetic.clone();
+            }
+
+            let found = AutoCorrect::collect(word.clone(), &ctx);
+            if found.len() >= k || depth == max_edit {
+                let mut ranked = self.rank(&word, found);
+                ranked.truncate(k);
+                return ranked;
+            }
+        }
+
+        Vec::new()
+    }
+
+    /// Same as `candidates` in the `SpaceSensitive` mode for a lowercase ASCII `word`, but found
+    /// by the multi-threaded fan-out of its variations the trie walk replaced. Only kept for the
+    /// benches to compare the two searches.
+    #[doc(hidden)]
+    pub fn candidates_fan_out(&self, word: String) -> Vec<Candidate> {
+        let (tx, rx) = channel::unbounded();
+        let store = Arc::new(Store::new());
+
+        dynamic::fan_out(word.clone(), 0, &self.context(), &self.pool, &store, &tx, 0);
+        drop(tx);
+
+        self.rank(&word, AutoCorrect::fewest_edits(rx))
+    }
+
+    /// Whether to keep the `word`, replace it with the top candidate, or suggest the best ones,
+    /// by the configured `DecisionPolicy`: the top candidate must lead the runner-up by the
+    /// policy's margin, the input must be long enough, and not a frequent word itself.
+    pub fn autocorrect(&self, word: String) -> Decision {
+        let input = word.trim().to_lowercase();
+        let frequency = self.dict.check(&input);
+
+        if frequency.is_none() && self.is_known(&input) {
+            return Decision::Keep;
+        }
+
+        self.config
+            .get_decision_policy()
+            .decide(&input, frequency, self.candidates(word))
+    }
+
+    /// Correct each word of the `sentence` in the context of the others: the candidates of all
+    /// the words are weighed together with the configured language model, and the most likely
+    /// sequence is kept. Without a language model, each word gets its best ranked candidate.
+    /// The words known without being listed in the dictionary, e.g. "don't", are kept as they
+    /// are. The text between the words is left untouched.
+    pub fn correct_sentence(&self, sentence: &str) -> String {
+        let tokens = common::tokenize(sentence);
+        let lattice: Vec<Vec<(String, f64)>> = tokens
+            .iter()
+            .map(|(_, token)| {
+                let lower = token.to_lowercase();
+
+                if self.dict.check(&lower).is_none() && self.is_known(&lower) {
+                    vec![(lower, 0.0)]
+                } else {
+                    sentence::states(token, self.candidates(lower))
+                }
+            })
+            .collect();
+
+        let choices = match self.language.as_ref() {
+            Some(model) => sentence::decode(&lattice, model),
+            None => vec![0; lattice.len()],
+        };
+
+        let mut result = String::with_capacity(sentence.len());
+        let mut last = 0;
+
+        for (((offset, token), states), choice) in tokens.iter().zip(lattice.iter()).zip(choices) {
+            let word = &states[choice].0;
+
+            result.push_str(&sentence[last..*offset]);
+            if *word == token.to_lowercase() {
+                result.push_str(token);
+            } else {
+                result.push_str(&self.recase(token, word));
+            }
+
+            last = offset + token.len();
+        }
+
+        result.push_str(&sentence[last..]);
+        result
+    }
+
+    /// The words of the `text` missing from the dictionary, with their byte ranges in the text
+    /// and their ranked candidates. With the joins on, two words apart by spaces only are
+    /// reported together if they're more likely a single known word. Feed the chosen fixes to
+    /// `apply` to rewrite the text. The words are told apart by the characters between them,
+    /// so a Chinese text isn't segmented, and each run of characters is checked as one word.
+    pub fn check_text(&self, text: &str) -> Vec<Issue> {
+        let tokens = common::tokenize(text);
+        let join_words = self.config.get_join_words();
+
+        let mut issues = Vec::new();
+        let mut idx = 0;
+
+        while idx < tokens.len() {
+            let (offset, token) = tokens[idx];
+
+            if let (true, Some((next_offset, next))) = (join_words, tokens.get(idx + 1)) {
+                let between = &text[offset + token.len()..*next_offset];
+
+                if between.chars().all(char::is_whitespace) {
+                    if let Some(joined) = split::join(token, next, &self.dict) {
+                        let span = offset..next_offset + next.len();
+                        let typed = &text[span.clone()];
+
+                        let mut joined = joined.trace(&typed.to_lowercase());
+                        joined.edit = joined.operations.len() as u8;
+                        joined.word = self.recase(token, &joined.word);
+
+                        issues.push(Issue { span, token: typed.to_owned(), suggestions: vec![joined] });
+                        idx += 2;
+                        continue;
+                    }
+                }
             }
+
+            // a Chinese text only has its Chinese words checked, the Latin ones in between, e.g.
+            // the names, are left as they are
+            let skipped = self.config.get_locale() == SupportedLocale::ZhCn
+                && !pinyin::has_han(token);
+
+            let lower = token.to_lowercase();
+            if !skipped && !self.is_known(&lower) {
+                issues.push(Issue {
+                    span: offset..offset + token.len(),
+                    token: token.to_owned(),
+                    suggestions: self.candidates(token.to_owned()),
+                });
+            }
+
+            idx += 1;
         }
 
-        stores::reset();
+        issues
+    }
 
-        vec.shrink_to_fit();
-        vec.sort_by(|a, b| b.cmp(a));
-        vec
+    /// The known words of the `text` that are likely typed in place of another word of their
+    /// confusion set, e.g. "their" for "there", judged by the configured language model on the
+    /// words around them. Needs both the confusion sets and the language model configured.
+    pub fn real_word_errors(&self, text: &str) -> Vec<RealWordError> {
+        match (self.confusion.as_ref(), self.language.as_ref()) {
+            (Some(sets), Some(model)) => confusion::detect(&common::tokenize(text), sets, model)
+                .into_iter()
+                .map(|mut error| {
+                    error.suggestion = self.recase(&error.word, &error.suggestion);
+                    error
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
     }
 
+    /// Send the candidates of the `word` to the `tx` as they're found, unranked. The search runs
+    /// on the service's worker pool, while the candidates are traced and cased on the calling
+    /// thread.
     pub fn candidates_async(&self, word: String, tx: mpsc::Sender<Candidate>) {
-        let max_edit = self.config.get_max_edit();
-        let locale = self.config.get_locale();
-        let (tx_cache, rx_cache) = channel::bounded(256);
+        let ctx = self.context();
+        let typed = word.trim().to_owned();
+        let input = typed.to_lowercase();
+        let (tx_cache, rx_cache) = channel::unbounded();
 
-        stores::get_ready();
-        AutoCorrect::run_job(move || {
-            dynamic::candidate(word, 0, max_edit, locale, &tx_cache, 0);
+        let job = AutoCorrect::run_job(&self.pool, move || {
+            AutoCorrect::search(word, &ctx, &tx_cache);
         });
 
+        if let Err(e) = job {
+            eprintln!("Failed to search the candidates: {}", e);
+            return;
+        }
+
         let mut cache = HashSet::with_capacity(256);
         for result in rx_cache {
             if !cache.contains(&result.word) {
                 cache.insert(result.word.clone());
 
+                let mut result = result.trace(&input);
+                result.word = self.recase(&typed, &result.word);
+
                 // send the result back, if the channel is closed, just return.
                 if tx.send(result).is_err() {
                     break;
                 }
             }
         }
+    }
+
+    /// Search the `word`, and keep the shortest edit of the words reached more than once.
+    fn collect(word: String, ctx: &Context) -> Vec<Candidate> {
+        let (tx, rx) = channel::unbounded();
+        AutoCorrect::search(word, ctx, &tx);
+        drop(tx);
 
-        stores::reset();
+        AutoCorrect::fewest_edits(rx)
     }
 
-    pub(crate) fn run_job<F: FnOnce() + Send + 'static>(f: F) {
-        if let Some(pool) = unsafe { POOL.as_mut() } {
-            if pool.exec(f, true).is_err() {
-                eprintln!("Failed to execute the search service...");
-            };
+    /// The received candidates, with the shortest edit of the words received more than once.
+    fn fewest_edits(rx: channel::Receiver<Candidate>) -> Vec<Candidate> {
+        let mut found: Vec<Candidate> = Vec::with_capacity(256);
+        let mut positions: HashMap<String, usize> = HashMap::with_capacity(256);
 
-            return;
+        for candidate in rx {
+            match positions.get(&candidate.word) {
+                Some(pos) => {
+                    if candidate.edit < found[*pos].edit {
+                        found[*pos] = candidate;
+                    }
+                },
+                None => {
+                    positions.insert(candidate.word.clone(), found.len());
+                    found.push(candidate);
+                },
+            }
+        }
+
+        found
+    }
+
+    /// Rank the candidates of the `typed` word, then case them after it.
+    fn rank(&self, typed: &str, candidates: Vec<Candidate>) -> Vec<Candidate> {
+        let typed = typed.trim();
+        let input = &typed.to_lowercase()[..];
+        let scorer = self.config.get_scorer();
+        let mut ranked: Vec<(f64, Candidate)> = candidates
+            .into_iter()
+            .map(|candidate| {
+                let mut candidate = candidate.trace(input);
+                candidate.probability = scorer.probability(input, &candidate);
+                (scorer.rank(input, &candidate), candidate)
+            })
+            .collect();
+
+        let total: f64 = ranked.iter().filter_map(|(_, candidate)| candidate.probability).sum();
+        if total > 0.0 {
+            for (_, candidate) in ranked.iter_mut() {
+                candidate.probability = candidate.probability.map(|chance| chance / total);
+            }
+        }
+
+        // the higher ranks first, and the ties in the alphabetical order
+        ranked.sort_by(|(rank_a, a), (rank_b, b)| {
+            rank_b
+                .partial_cmp(rank_a)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| a.word.cmp(&b.word))
+        });
+
+        ranked
+            .into_iter()
+            .map(|(_, mut candidate)| {
+                candidate.word = self.recase(typed, &candidate.word);
+                candidate
+            })
+            .collect()
+    }
+
+    /// The lowercase `word` cased after the `typed` one, or as spelled in the dictionary.
+    fn recase(&self, typed: &str, word: &str) -> String {
+        case::recase(typed, word, self.dict.canonical(word))
+    }
+
+    /// Whether the lowercase `word` is known: listed in the dictionary, a compound of known words
+    /// if those are accepted, or made of known words joined by hyphens, e.g. "well-known". The
+    /// dictionaries may list the contractions without their apostrophes, e.g. "dont", and not
+    /// list the possessives at all, so a word is also known if it is once its apostrophes or its
+    /// final "'s" are dropped, e.g. "don't" or "john's".
+    fn is_known(&self, word: &str) -> bool {
+        if self.dict.check(word).is_some() || self.is_compound(word) {
+            return true;
+        }
+
+        if word.contains('\'') {
+            let bare = word.replace('\'', "");
+            let stem = word.strip_suffix("'s");
+
+            return self.is_known(&bare) || stem.is_some_and(|stem| self.is_known(stem));
         }
 
-        eprintln!("Failed to execute the service...");
+        word.contains('-') && word.split('-').all(|part| self.dict.check(part).is_some())
     }
 
-    fn init_dict(&self) {
-        match self.config.get_run_mode() {
-            RunMode::SpeedSensitive => hybrid::initialize(&self),
-            RunMode::SpaceSensitive => dynamic::initialize(&self),
+    /// Whether the lowercase `word` is a compound of known words, with the compounds accepted.
+    fn is_compound(&self, word: &str) -> bool {
+        self.config.get_compound_words() && split::compound(word, &self.dict)
+    }
+
+    pub(crate) fn run_job<F>(pool: &ThreadPool, f: F) -> Result<(), Error>
+        where F: FnOnce() + Send + 'static
+    {
+        let reason = match pool.execute(f) {
+            Ok(()) => return Ok(()),
+            Err(ExecutionError::Timeout) => "timed out",
+            Err(ExecutionError::Disconnected) => "pool disconnected",
+            Err(ExecutionError::PoolPoisoned) => "pool poisoned",
+        };
+
+        Err(Error::Executor(reason.to_owned()))
+    }
+
+    fn search(word: String, ctx: &Context, tx: &channel::Sender<Candidate>) {
+        let phonetic = ctx.phonetic.as_ref().map(|index| (index, word.clone()));
+        let split = if ctx.split_words { Some(word.clone()) } else { None };
+
+        match ctx.index.as_ref() {
+            // the pinyin stands in for the edits, which don't model the typos of the characters
+            _ if ctx.pinyin.is_some() => {
+                if let Some(index) = ctx.pinyin.as_ref() {
+                    pinyin::candidate(word, ctx.max_edit, &ctx.dict, index, tx);
+                }
+            },
+            Index::Neighbors(reverse_dict) => hybrid::candidate(
+                word, ctx.max_edit, ctx.locale, &ctx.dict, reverse_dict, tx
+            ),
+            Index::Deletes(deletes) => symspell::candidate(word, ctx.max_edit, deletes, tx),
+            Index::BkTree(tree) => bktree::candidate(word, ctx.max_edit, tree, tx),
+            Index::Empty if ctx.run_mode == RunMode::Automaton => {
+                automaton::candidate(word, ctx, tx)
+            },
+            Index::Empty => dynamic::candidate(word, ctx, tx),
         }
+
+        if let Some(word) = split {
+            split::candidate(word, ctx.max_edit, &ctx.dict, tx);
+        }
+
+        // the edit matches come first, so a word found both ways is kept as an edit match
+        if let Some((index, word)) = phonetic {
+            phonetic::candidate(word, &ctx.dict, index, tx);
+        }
+    }
+
+    fn context(&self) -> Context {
+        Context {
+            dict: Arc::clone(&self.dict),
+            index: Arc::clone(&self.index),
+            max_edit: self.config.get_max_edit(),
+            locale: self.config.get_locale(),
+            run_mode: self.config.get_run_mode(),
+            phonetic: self.phonetic.clone(),
+            pinyin: self.pinyin.clone(),
+            split_words: self.config.get_split_words(),
+        }
+    }
+
+    fn init_dict(&mut self) -> Result<(), Error> {
+        // all modes look up the words' scores from the trie
+        let dict = dynamic::initialize(&self.config, &self.pool)?;
+
+        let pinyin = if self.config.get_locale() == SupportedLocale::ZhCn {
+            Some(PinyinIndex::build(self.config.get_pinyin_source(), &dict)?)
+        } else {
+            None
+        };
+
+        let index = match self.config.get_run_mode() {
+            _ if pinyin.is_some() => Index::Empty,
+            RunMode::SpeedSensitive => {
+                Index::Neighbors(hybrid::initialize(&self.config, &self.pool, &dict)?)
+            },
+            RunMode::SymmetricDelete => {
+                Index::Deletes(DeleteIndex::build(&dict, self.config.get_max_edit()))
+            },
+            RunMode::BkTree => Index::BkTree(BkTree::build(&dict)),
+            RunMode::SpaceSensitive | RunMode::Automaton => Index::Empty,
+        };
+
+        let phonetic = self.config
+            .get_phonetic()
+            .map(|algorithm| Arc::new(PhoneticIndex::build(&dict, algorithm)));
+
+        self.load_language_model()?;
+        self.load_confusion_sets()?;
+
+        // swapped in only once all loaded, so a failure keeps the former dictionary in use
+        self.dict = Arc::new(dict);
+        self.phonetic = phonetic;
+        self.index = Arc::new(index);
+        self.pinyin = pinyin.map(Arc::new);
+        Ok(())
+    }
+
+    fn load_language_model(&mut self) -> Result<(), Error> {
+        let path = self.config.get_language_model();
+
+        self.language = if path.is_empty() {
+            None
+        } else {
+            Some(Arc::new(LanguageModel::load(&path)?))
+        };
+
+        Ok(())
+    }
+
+    fn load_confusion_sets(&mut self) -> Result<(), Error> {
+        let path = self.config.get_confusion_sets();
+
+        self.confusion = if path.is_empty() {
+            None
+        } else {
+            Some(Arc::new(ConfusionSets::load(&path)?))
+        };
+
+        Ok(())
     }
 
-    fn pool_init(size: usize) {
-        unsafe { POOL.replace(ThreadPool::new(size)); }
+    fn reload_dict(&mut self) {
+        if let Err(e) = self.init_dict() {
+            eprintln!("Failed to reload the dictionary: {}", e);
+        }
     }
 }
 
@@ -146,6 +620,13 @@ impl AutoCorrectConfig for AutoCorrect {
         }
 
         self.config.set_max_edit(max_edit);
+
+        // the delete index only covers the max edits it's built for
+        if let Index::Deletes(deletes) = self.index.as_ref() {
+            if deletes.depth() < self.config.get_max_edit() {
+                self.reload_dict();
+            }
+        }
     }
 
     #[inline]
@@ -154,7 +635,18 @@ impl AutoCorrectConfig for AutoCorrect {
     }
 
     fn set_pool_size(&mut self, pool_size: usize) {
+        if pool_size == self.config.get_pool_size() {
+            return;
+        }
+
+        if pool_size == 0 {
+            eprintln!("Failed to resize the pool: the pool size must be greater than 0");
+            return;
+        }
+
+        // the queries already running keep the old pool alive until they're done
         self.config.set_pool_size(pool_size);
+        self.pool = Arc::new(ThreadPool::new(self.config.get_pool_size()));
     }
 
     #[inline]
@@ -167,10 +659,14 @@ impl AutoCorrectConfig for AutoCorrect {
             return;
         }
 
+        let prev = self.config.get_locale();
         self.config.set_locale(locale);
 
-        if !self.config.get_override_dict().is_empty() {
-            self.init_dict();
+        // the locale's own dictionary, or the override one searched with the locale's alphabet;
+        // if it fails to load, the former locale stays along with its dictionary
+        if let Err(e) = self.init_dict() {
+            eprintln!("Failed to switch the locale: {}", e);
+            self.config.set_locale(prev);
         }
     }
 
@@ -185,7 +681,7 @@ impl AutoCorrectConfig for AutoCorrect {
         }
 
         self.config.set_run_mode(mode);
-        self.init_dict();
+        self.reload_dict();
     }
 
     #[inline]
@@ -198,51 +694,195 @@ impl AutoCorrectConfig for AutoCorrect {
             return;
         }
 
+        // the reverse dict of an override dictionary is generated while loading it
         self.config.set_override_dict(dict_path);
-        self.init_dict();
-
-        if self.config.get_run_mode() == RunMode::SpeedSensitive {
-            if let Err(e) = self.refresh_hybrid_dict(None) {
-                eprintln!("Encountering error while updating the override dict: {}", e);
-            }
-        }
+        self.reload_dict();
     }
 
     #[inline]
     fn get_override_dict(&self) -> String {
         self.config.get_override_dict()
     }
+
+    fn set_scorer(&mut self, scorer: Arc<dyn Scorer>) {
+        self.config.set_scorer(scorer);
+    }
+
+    #[inline]
+    fn get_scorer(&self) -> Arc<dyn Scorer> {
+        self.config.get_scorer()
+    }
+
+    fn set_phonetic(&mut self, phonetic: Option<Phonetic>) {
+        if phonetic == self.config.get_phonetic() {
+            return;
+        }
+
+        // only the phonetic index changes, the loaded dictionary stays
+        self.config.set_phonetic(phonetic);
+        self.phonetic = phonetic.map(|algorithm| Arc::new(PhoneticIndex::build(&self.dict, algorithm)));
+    }
+
+    #[inline]
+    fn get_phonetic(&self) -> Option<Phonetic> {
+        self.config.get_phonetic()
+    }
+
+    fn set_language_model(&mut self, model_path: &str) {
+        if model_path == self.config.get_language_model() {
+            return;
+        }
+
+        self.config.set_language_model(model_path);
+
+        if let Err(e) = self.load_language_model() {
+            eprintln!("Failed to load the language model: {}", e);
+            self.language = None;
+        }
+    }
+
+    #[inline]
+    fn get_language_model(&self) -> String {
+        self.config.get_language_model()
+    }
+
+    fn set_confusion_sets(&mut self, sets_path: &str) {
+        if sets_path == self.config.get_confusion_sets() {
+            return;
+        }
+
+        self.config.set_confusion_sets(sets_path);
+
+        if let Err(e) = self.load_confusion_sets() {
+            eprintln!("Failed to load the confusion sets: {}", e);
+            self.confusion = None;
+        }
+    }
+
+    #[inline]
+    fn get_confusion_sets(&self) -> String {
+        self.config.get_confusion_sets()
+    }
+
+    fn set_decision_policy(&mut self, policy: DecisionPolicy) {
+        self.config.set_decision_policy(policy);
+    }
+
+    #[inline]
+    fn get_decision_policy(&self) -> DecisionPolicy {
+        self.config.get_decision_policy()
+    }
+
+    fn set_split_words(&mut self, split: bool) {
+        self.config.set_split_words(split);
+    }
+
+    #[inline]
+    fn get_split_words(&self) -> bool {
+        self.config.get_split_words()
+    }
+
+    fn set_join_words(&mut self, join: bool) {
+        self.config.set_join_words(join);
+    }
+
+    #[inline]
+    fn get_join_words(&self) -> bool {
+        self.config.get_join_words()
+    }
+
+    fn set_compound_words(&mut self, compound: bool) {
+        self.config.set_compound_words(compound);
+    }
+
+    #[inline]
+    fn get_compound_words(&self) -> bool {
+        self.config.get_compound_words()
+    }
 }
 
 pub trait ServiceUtils {
-    fn refresh_hybrid_dict(&self, custom_path: Option<String>) -> Result<(), String>;
+    /// Regenerate the reverse dict for the `SpeedSensitive` mode from the loaded dictionary, and
+    /// save it to the `custom_path` if one is given.
+    fn refresh_hybrid_dict(&mut self, custom_path: Option<String>) -> Result<(), Error>;
 }
 
 impl ServiceUtils for AutoCorrect {
-    fn refresh_hybrid_dict(&self, _custom_path: Option<String>) -> Result<(), String> {
-        //TODO: now compress and save the result to disk
-
-/*        let dict =
-            common::generate_reverse_dict(&self.config);
+    fn refresh_hybrid_dict(&mut self, custom_path: Option<String>) -> Result<(), Error> {
+        let reverse_dict = ReverseDict::generate(&self.dict, self.config.get_locale());
 
+        if let Some(path) = custom_path {
+            reverse_dict.save(&path)?;
+        }
 
         if self.config.get_run_mode() == RunMode::SpeedSensitive {
-            hybrid::set_reverse_dict(dict);
-        }*/
+            self.index = Arc::new(Index::Neighbors(reverse_dict));
+        }
 
         Ok(())
     }
 }
 
+#[cfg(test)]
+mod test_utils {
+    use super::*;
+    use std::fs;
+    use std::process;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static FIXTURES: AtomicUsize = AtomicUsize::new(0);
+
+    /// Write the `content` to a new file in the temp directory and return its path. Each call
+    /// gets its own file, so the tests running in parallel never overwrite the fixtures of
+    /// one another.
+    pub(crate) fn temp_file(label: &str, content: &str) -> String {
+        let id = FIXTURES.fetch_add(1, Ordering::SeqCst);
+        let name = format!("auto_correct_{}_{}_{}.txt", label, process::id(), id);
+        let path = std::env::temp_dir().join(name);
+
+        fs::write(&path, content).unwrap();
+        path.to_str().unwrap().to_owned()
+    }
+
+    /// The config of a dictionary holding the 5000 most frequent words of the en-us one, so the
+    /// tests don't pay for indexing the whole dictionary.
+    pub(crate) fn top_words_config(label: &str, run_mode: RunMode, max_edit: u8) -> Config {
+        let source = fs::read_to_string("./resources/en-us/freq_50k.txt").unwrap();
+        let content: Vec<&str> = source.lines().take(5000).map(|line| line.trim()).collect();
+        let path = temp_file(label, &content.join("\n"));
+
+        Config::new_with_params(max_edit, 2, SupportedLocale::EnUs, run_mode, &path)
+    }
+
+    /// The words of the candidates, sorted.
+    pub(crate) fn words_of(candidates: &[Candidate]) -> Vec<String> {
+        let mut words: Vec<String> = candidates.iter().map(|c| c.word.clone()).collect();
+        words.sort();
+        words
+    }
+
+    /// The words of the candidates with their edits and scores, in the order of the candidates.
+    pub(crate) fn summary(candidates: &[Candidate]) -> Vec<(String, u8, u32)> {
+        candidates.iter().map(|c| (c.word.to_owned(), c.edit, c.score)).collect()
+    }
+
+    /// The word of the best ranked candidate.
+    pub(crate) fn first(service: &AutoCorrect, word: &str) -> String {
+        service.candidates(word.to_owned())[0].word.to_owned()
+    }
+}
+
 #[cfg(test)]
 mod tests_dyn {
     use super::*;
+    use test_utils::{summary, temp_file};
+    use std::thread;
 
     #[test]
     fn base() {
         let mut service = AutoCorrect::new();
         service.set_max_edit(2);
-        assert_eq!(service.candidates(String::from("tets")).len(), 360usize);
+        assert_eq!(service.candidates(String::from("tets")).len(), 372usize);
     }
 
     #[test]
@@ -258,4 +898,1575 @@ mod tests_dyn {
         service.set_max_edit(2);
         assert!(service.candidates(String::from("whataboutism")).is_empty());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn fan_out() {
+        // the search the walk replaced finds none but the words of the walk, with the same
+        // edits, though it skips a few of them, e.g. "tess" for "tets"
+        let service = AutoCorrect::new();
+
+        for word in ["tets", "wodr", "speling"].iter() {
+            let walk = summary(&service.candidates(word.to_string()));
+            let fan_out = summary(&service.candidates_fan_out(word.to_string()));
+
+            assert!(!fan_out.is_empty());
+            assert!(fan_out.iter().all(|found| walk.contains(found)), "mismatch for {}", word);
+        }
+    }
+
+    #[test]
+    fn true_distance() {
+        let mut service = AutoCorrect::new();
+        service.set_max_edit(2);
+
+        for word in ["tets", "ca", "wodr", "speling"].iter() {
+            let mut expected: Vec<(String, u8)> = service.dict
+                .words()
+                .into_iter()
+                .map(|(known, _)| {
+                    let distance = common::damerau_distance(word, &known);
+                    (known, distance as u8)
+                })
+                .filter(|(_, distance)| *distance <= 2)
+                .collect();
+
+            let mut found: Vec<(String, u8)> = service
+                .candidates(word.to_string())
+                .into_iter()
+                .map(|c| (c.word, c.edit))
+                .collect();
+
+            expected.sort();
+            found.sort();
+            assert_eq!(found, expected, "mismatch for {}", word);
+        }
+    }
+
+    #[test]
+    fn independent_dicts() {
+        let path = temp_file("independent_dicts", "tetsy,10\n");
+
+        let mut custom = AutoCorrect::new_with_config(Config::new_with_params(
+            1, 2, SupportedLocale::EnUs, RunMode::SpaceSensitive, &path
+        ));
+        custom.set_max_edit(1);
+
+        let mut service = AutoCorrect::new();
+        service.set_max_edit(1);
+
+        let words: Vec<String> = custom.candidates(String::from("tets"))
+            .into_iter()
+            .map(|candidate| candidate.word)
+            .collect();
+
+        assert_eq!(words, vec![String::from("tetsy")]);
+        assert!(service.candidates(String::from("tets")).iter().all(|c| c.word != "tetsy"));
+        assert!(!service.candidates(String::from("tets")).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "en-us")]
+    fn embedded_dict() {
+        match Config::new().get_dict_source() {
+            Ok(config::DictSource::Embedded(content)) => assert!(content.starts_with("you,")),
+            _ => panic!("expecting the embedded dictionary"),
+        }
+    }
+
+    #[test]
+    fn send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<AutoCorrect>();
+    }
+
+    #[test]
+    fn concurrent() {
+        let mut service = AutoCorrect::new();
+        service.set_max_edit(2);
+
+        let words = ["tets", "wahtabout", "helo", "wodr", "speling"];
+
+        let expected: Vec<_> = words
+            .iter()
+            .map(|word| summary(&service.candidates(word.to_string())))
+            .collect();
+
+        let service = Arc::new(service);
+        let expected = Arc::new(expected);
+
+        let handles: Vec<_> = (0..8)
+            .map(|id| {
+                let service = Arc::clone(&service);
+                let expected = Arc::clone(&expected);
+
+                thread::spawn(move || {
+                    for round in 0..words.len() {
+                        let idx = (id + round) % words.len();
+                        let res = summary(&service.candidates(words[idx].to_string()));
+                        assert_eq!(res, expected[idx], "mismatch for {}", words[idx]);
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_error {
+    use super::*;
+    use test_utils::temp_file;
+
+    fn config_with_dict(path: &str) -> Config {
+        Config::new_with_params(1, 2, SupportedLocale::EnUs, RunMode::SpaceSensitive, path)
+    }
+
+    #[test]
+    fn missing_dict() {
+        match AutoCorrect::try_new_with_config(config_with_dict("./resources/not-a-dict.txt")) {
+            Err(Error::MissingDictionary(path)) => assert_eq!(path, "./resources/not-a-dict.txt"),
+            _ => panic!("expecting a missing dictionary error"),
+        }
+    }
+
+    #[test]
+    fn malformed_line() {
+        let path = temp_file("malformed_line", "test,10\ntets,not-a-score\n");
+
+        match AutoCorrect::try_new_with_config(config_with_dict(&path)) {
+            Err(Error::MalformedLine { line, content }) => {
+                assert_eq!(line, 2);
+                assert_eq!(content, "tets,not-a-score");
+            },
+            _ => panic!("expecting a malformed line error"),
+        }
+    }
+
+    #[test]
+    fn io_error() {
+        let path = temp_file("io_error", "test,10\ntext,8\n");
+
+        let mut service = AutoCorrect::try_new_with_config(config_with_dict(&path)).unwrap();
+        let target = std::env::temp_dir().join("auto_correct_no_such_dir").join("neighbors.txt");
+        let target = target.to_str().unwrap().to_owned();
+
+        match service.refresh_hybrid_dict(Some(target.clone())) {
+            Err(Error::Io { path, .. }) => assert_eq!(path, target),
+            _ => panic!("expecting an io error"),
+        }
+    }
+
+    #[test]
+    fn unreadable_dict() {
+        // a line that isn't valid UTF-8 fails the loading, rather than cutting the dictionary
+        let path = temp_file("unreadable_dict", "");
+        std::fs::write(&path, b"test,10\n\xff\xfe,5\ntext,8\n").unwrap();
+
+        match AutoCorrect::try_new_with_config(config_with_dict(&path)) {
+            Err(Error::Io { path: failed, .. }) => assert_eq!(failed, path),
+            _ => panic!("expecting an io error"),
+        }
+
+        // a path that's there but can't be read as a file isn't a missing one
+        let dir = std::env::temp_dir();
+        let dir = dir.to_str().unwrap();
+
+        match AutoCorrect::try_new_with_config(config_with_dict(dir)) {
+            Err(Error::Io { path, .. }) => assert_eq!(path, dir),
+            _ => panic!("expecting an io error"),
+        }
+    }
+
+    #[test]
+    fn invalid_config() {
+        assert!(Config::try_new_with_params(
+            4, 2, SupportedLocale::EnUs, RunMode::SpaceSensitive, ""
+        ).is_err());
+
+        let config = Config::new_with_params(1, 0, SupportedLocale::EnUs, RunMode::SpaceSensitive, "");
+        match AutoCorrect::try_new_with_config(config) {
+            Err(Error::InvalidConfig(_)) => {},
+            _ => panic!("expecting an invalid config error"),
+        }
+
+        for max_edit in [0, 4].iter() {
+            let path = temp_file("invalid_config", "test,10\n");
+            let config = Config::new_with_params(
+                *max_edit, 2, SupportedLocale::EnUs, RunMode::SpaceSensitive, &path
+            );
+
+            match AutoCorrect::try_new_with_config(config.clone()) {
+                Err(Error::InvalidConfig(_)) => {},
+                _ => panic!("expecting the max edit {} to be rejected", max_edit),
+            }
+
+            // the lenient constructor brings it back into the range instead
+            assert_eq!(AutoCorrect::new_with_config(config).get_max_edit(), (*max_edit).clamp(1, 3));
+        }
+    }
+
+    #[test]
+    fn missing_file() {
+        let mut config = config_with_dict(&temp_file("missing_file", "test,10\n"));
+        config.set_language_model("./resources/not-a-model.txt");
+
+        match AutoCorrect::try_new_with_config(config) {
+            Err(Error::MissingFile(path)) => assert_eq!(path, "./resources/not-a-model.txt"),
+            _ => panic!("expecting a missing file error"),
+        }
+    }
+
+    #[test]
+    fn valid_config() {
+        assert!(AutoCorrect::try_new_with_config(Config::new()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests_hybrid {
+    use super::*;
+    use test_utils::{temp_file, words_of};
+    use std::fs;
+
+    #[test]
+    fn covers_dynamic_within_one_edit() {
+        let mut hybrid = AutoCorrect::new();
+        hybrid.set_run_mode(RunMode::SpeedSensitive);
+
+        let dynamic = AutoCorrect::new();
+
+        for word in ["tets", "wodr", "helo"].iter() {
+            let found = words_of(&hybrid.candidates(word.to_string()));
+
+            for expected in words_of(&dynamic.candidates(word.to_string())) {
+                assert!(found.contains(&expected), "missing {} for {}", expected, word);
+            }
+        }
+    }
+
+    #[test]
+    fn composed_edits() {
+        let mut service = AutoCorrect::new();
+        service.set_run_mode(RunMode::SpeedSensitive);
+        service.set_max_edit(2);
+
+        let results = service.candidates(String::from("disk"));
+        let find = |word: &str| results.iter().find(|c| c.word == word).map(|c| c.edit);
+
+        assert_eq!(find("disk"), Some(0));
+        assert_eq!(find("desk"), Some(1));
+        assert_eq!(find("desks"), Some(2));
+        assert!(results.iter().all(|c| c.edit <= 2));
+    }
+
+    #[test]
+    fn deeper_edits_within_dynamic() {
+        let mut hybrid = AutoCorrect::new();
+        hybrid.set_run_mode(RunMode::SpeedSensitive);
+
+        let mut dynamic = AutoCorrect::new();
+
+        for max_edit in 2..=3 {
+            hybrid.set_max_edit(max_edit);
+            dynamic.set_max_edit(max_edit);
+
+            for word in ["tets", "wodr", "disk"].iter() {
+                let expected = dynamic.candidates(word.to_string());
+
+                for found in hybrid.candidates(word.to_string()) {
+                    let same = expected.iter().find(|c| c.word == found.word);
+                    assert!(same.is_some(), "unexpected {} for {}", found.word, word);
+                    assert_eq!(same.unwrap().edit, found.edit, "edit of {} for {}", found.word, word);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn override_dict() {
+        let dict = temp_file("hybrid_override", "test,30\ntest,30\ntent,20\nrent,10\n");
+        let saved = temp_file("hybrid_saved", "");
+
+        let config = Config::new_with_params(
+            2, 2, SupportedLocale::EnUs, RunMode::SpeedSensitive, &dict
+        );
+
+        let mut service = AutoCorrect::try_new_with_config(config).unwrap();
+        assert_eq!(
+            words_of(&service.candidates(String::from("tets"))),
+            vec![String::from("tent"), String::from("test")]
+        );
+
+        service.refresh_hybrid_dict(Some(saved.to_owned())).unwrap();
+
+        let mut lines: Vec<String> = fs::read_to_string(&saved)
+            .unwrap()
+            .lines()
+            .map(|line| line.to_owned())
+            .collect();
+        lines[1..].sort();
+
+        assert_eq!(lines[0], "#auto_correct:neighbors:v1");
+        assert_eq!(&lines[1..], &["rent^tent;", "tent^rent;test;", "test^tent;"]);
+    }
+
+    #[test]
+    fn unsupported_index_version() {
+        let (tx, rx) = channel::unbounded();
+        tx.send(Ok((1, String::from("#auto_correct:neighbors:v0")))).unwrap();
+        tx.send(Ok((2, String::from("test^tent;")))).unwrap();
+        drop(tx);
+
+        match ReverseDict::build(rx) {
+            Err(Error::MalformedLine { line, .. }) => assert_eq!(line, 1),
+            _ => panic!("expecting the index version to be rejected"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_symspell {
+    use super::*;
+    use test_utils::{top_words_config, words_of};
+
+    #[test]
+    fn distance() {
+        assert_eq!(common::damerau_distance("tets", "test"), 1);
+        assert_eq!(common::damerau_distance("tets", "tets"), 0);
+        assert_eq!(common::damerau_distance("", "abc"), 3);
+        assert_eq!(common::damerau_distance("ca", "abc"), 2);
+        assert_eq!(common::damerau_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn covers_dynamic() {
+        let deletes = top_words_config("symspell_deletes", RunMode::SymmetricDelete, 2);
+        let dynamic = top_words_config("symspell_dynamic", RunMode::SpaceSensitive, 2);
+        let deletes = AutoCorrect::new_with_config(deletes);
+        let dynamic = AutoCorrect::new_with_config(dynamic);
+
+        // the words longer than the indexed prefix are told apart by the verification
+        for word in ["tets", "wahtabout", "speling", "govenrment", "informaton"].iter() {
+            let found = deletes.candidates(word.to_string());
+            let found_words = words_of(&found);
+
+            for expected in words_of(&dynamic.candidates(word.to_string())) {
+                assert!(found_words.contains(&expected), "missing {} for {}", expected, word);
+            }
+
+            for candidate in found.iter() {
+                let distance = common::damerau_distance(word, &candidate.word);
+                assert_eq!(candidate.edit as usize, distance);
+                assert!(distance <= 2);
+            }
+        }
+    }
+
+    #[test]
+    fn deeper_index_on_demand() {
+        let config = top_words_config("symspell_deeper", RunMode::SymmetricDelete, 1);
+        let mut service = AutoCorrect::new_with_config(config);
+
+        assert!(service.candidates(String::from("tets")).iter().all(|c| c.edit <= 1));
+
+        service.set_max_edit(2);
+        assert!(service.candidates(String::from("tets")).iter().any(|c| c.edit == 2));
+    }
+}
+
+#[cfg(test)]
+mod tests_automaton {
+    use super::*;
+    use test_utils::summary;
+    use automaton::Automaton;
+
+    fn feed(automaton: &mut Automaton, word: &str) -> Option<u8> {
+        let mut state = automaton.start();
+
+        for rune in word.chars() {
+            state = automaton.step(state, rune)?;
+        }
+
+        automaton.distance(state)
+    }
+
+    #[test]
+    fn distance() {
+        let pairs = [("ca", "abc"), ("tets", "test"), ("kitten", "sitting"), ("abcd", "badc")];
+
+        for (source, target) in pairs.iter() {
+            let expected = common::damerau_distance(source, target);
+
+            for max_edit in 1..=3u8 {
+                let mut automaton = Automaton::new(source, max_edit);
+                let distance = feed(&mut automaton, target);
+
+                if expected <= max_edit as usize {
+                    assert_eq!(distance, Some(expected as u8), "{} -> {}", source, target);
+                } else {
+                    assert_eq!(distance, None, "{} -> {}", source, target);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn equivalence() {
+        let mut automaton = AutoCorrect::new();
+        automaton.set_run_mode(RunMode::Automaton);
+
+        let mut dynamic = AutoCorrect::new();
+
+        for max_edit in 1..=3u8 {
+            automaton.set_max_edit(max_edit);
+            dynamic.set_max_edit(max_edit);
+
+            for word in ["tets", "wahtabout", "speling", "ca", "acress"].iter() {
+                assert_eq!(
+                    summary(&automaton.candidates(word.to_string())),
+                    summary(&dynamic.candidates(word.to_string())),
+                    "mismatch for {} within {} edits", word, max_edit
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_bktree {
+    use super::*;
+    use test_utils::{summary, top_words_config};
+    use metric::{Damerau, KeyboardWeighted, Levenshtein, Metric};
+
+    #[test]
+    fn metrics() {
+        assert_eq!(Levenshtein.distance("tets", "test"), 2);
+        assert_eq!(Damerau.distance("tets", "test"), 1);
+        assert_eq!(Levenshtein.distance("kitten", "sitting"), 3);
+
+        let keyboard = KeyboardWeighted::default();
+        assert_eq!(keyboard.distance("gest", "test"), 1);
+        assert_eq!(keyboard.distance("gest", "rest"), 2);
+        assert_eq!(keyboard.distance("gest", "ges"), 2);
+    }
+
+    #[test]
+    fn keyboard_weights() {
+        assert!(KeyboardWeighted::qwerty(1, 2, 2).is_ok());
+        assert!(KeyboardWeighted::qwerty(2, 3, 2).is_ok());
+
+        // each breaks the triangle inequality, or lets different words be 0 apart
+        for (near, far, indel) in [(0, 1, 1), (1, 1, 0), (2, 1, 2), (1, 3, 2), (2, 3, 1)].iter() {
+            match KeyboardWeighted::qwerty(*near, *far, *indel) {
+                Err(Error::InvalidConfig(_)) => {},
+                _ => panic!("expecting the weights {:?} to be rejected", (near, far, indel)),
+            }
+        }
+    }
+
+    #[test]
+    fn user_words() {
+        let words = vec![
+            (String::from("test"), 3),
+            (String::from("rest"), 2),
+            (String::from("zest"), 1),
+        ];
+
+        let mut tree = BkTree::from_words(KeyboardWeighted::default(), words);
+        tree.insert(String::from("test"), 5);
+        assert_eq!(tree.len(), 3);
+
+        assert_eq!(summary(&tree.find("gest", 1)), vec![(String::from("test"), 1, 5)]);
+        assert_eq!(tree.find("gest", 2).len(), 3);
+
+        let lengths = BkTree::from_words(
+            |a: &str, b: &str| (a.len() as i64 - b.len() as i64).unsigned_abs() as u32,
+            vec![(String::from("a"), 1), (String::from("abc"), 1)],
+        );
+
+        assert_eq!(lengths.find("xy", 1).len(), 2);
+    }
+
+    #[test]
+    fn equivalence() {
+        let tree = top_words_config("bktree", RunMode::BkTree, 2);
+        let dynamic = top_words_config("bktree_dynamic", RunMode::SpaceSensitive, 2);
+        let tree = AutoCorrect::new_with_config(tree);
+        let dynamic = AutoCorrect::new_with_config(dynamic);
+
+        for word in ["tets", "wahtabout", "speling", "ca"].iter() {
+            assert_eq!(
+                summary(&tree.candidates(word.to_string())),
+                summary(&dynamic.candidates(word.to_string())),
+                "mismatch for {}", word
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_scorer {
+    use super::*;
+
+    #[test]
+    fn default_ranking() {
+        let mut service = AutoCorrect::new();
+        service.set_max_edit(2);
+
+        let results = service.candidates(String::from("tets"));
+        assert!(!results.is_empty());
+
+        for pair in results.windows(2) {
+            assert!(
+                pair[0].edit < pair[1].edit
+                    || (pair[0].edit == pair[1].edit && pair[0].score >= pair[1].score),
+                "{:?} ranked before {:?}", pair[0], pair[1]
+            );
+        }
+    }
+
+    #[test]
+    fn custom_scorer() {
+        let domain = |_: &str, candidate: &Candidate| {
+            if candidate.word == "tests" { 1.0 } else { -f64::from(candidate.edit) }
+        };
+
+        let mut config = Config::new();
+        config.set_scorer(Arc::new(domain));
+
+        let service = AutoCorrect::new_with_config(config.clone());
+        let results = service.candidates(String::from("tets"));
+
+        assert_eq!(results[0].word, "tests");
+        assert_eq!(config.get_scorer().rank("tets", &results[0]), 1.0);
+    }
+}
+
+#[cfg(test)]
+mod tests_keyboard {
+    use super::*;
+    use keyboard::Keyboard;
+    use scorer::KeyboardScorer;
+
+    #[test]
+    fn layouts() {
+        let qwerty = Keyboard::qwerty();
+        assert!(qwerty.is_near('e', 'r'));
+        assert!(qwerty.is_near('g', 't') && qwerty.is_near('g', 'b'));
+        assert!(!qwerty.is_near('e', 'p'));
+        assert!(!qwerty.is_near('g', 'r'));
+
+        assert!(Keyboard::azerty().is_near('a', 'z'));
+        assert!(!Keyboard::azerty().is_near('a', 's'));
+        assert!(Keyboard::qwertz().is_near('z', 't'));
+        assert!(Keyboard::qwertz().is_near('y', 's'));
+        assert!(Keyboard::dvorak().is_near('e', 'u'));
+        assert!(!Keyboard::dvorak().is_near('e', 'r'));
+
+        let grid = Keyboard::from_rows(&["abc", " de"]);
+        assert!(grid.is_near('d', 'b') && grid.is_near('d', 'c'));
+        assert!(!grid.is_near('d', 'a'));
+    }
+
+    #[test]
+    fn typo_cost() {
+        let scorer = KeyboardScorer::new(Keyboard::qwerty());
+
+        assert_eq!(scorer.typo_cost("tets", "test"), 1.0);
+        assert_eq!(scorer.typo_cost("tets", "tests"), 1.0);
+        assert_eq!(scorer.typo_cost("tets", "gets"), 1.25);
+        assert_eq!(scorer.typo_cost("tets", "lets"), 2.0);
+    }
+
+    #[test]
+    fn ranking() {
+        let mut service = AutoCorrect::new();
+        assert_eq!(service.candidates(String::from("tets"))[0].word, "gets");
+
+        service.set_scorer(Arc::new(KeyboardScorer::new(Keyboard::qwerty())));
+        let words: Vec<String> = service
+            .candidates(String::from("tets"))
+            .into_iter()
+            .map(|c| c.word)
+            .collect();
+
+        let position = |word: &str| words.iter().position(|found| found == word).unwrap();
+
+        assert_eq!(words[..2], [String::from("test"), String::from("tests")]);
+        assert!(position("gets") < position("lets"));
+    }
+}
+
+#[cfg(test)]
+mod tests_noisy {
+    use super::*;
+    use test_utils::temp_file;
+    use noisy::{ErrorModel, NoisyChannel};
+    use operation::{operations, Operation};
+
+    #[test]
+    fn alignment() {
+        assert_eq!(operations("tets", "test"), vec![Operation::Transpose(2, 3)]);
+        assert_eq!(operations("tets", "tets"), vec![]);
+        assert_eq!(
+            operations("tets", "tests"),
+            vec![Operation::Insert { pos: 2, rune: 's' }]
+        );
+        assert_eq!(
+            operations("ca", "abc"),
+            vec![Operation::Transpose(0, 1), Operation::Insert { pos: 1, rune: 'b' }]
+        );
+
+        let pairs = [("kitten", "sitting"), ("wahtabout", "whatabout"), ("abcd", "badc")];
+        for (source, target) in pairs.iter() {
+            assert_eq!(operations(source, target).len(), common::damerau_distance(source, target));
+        }
+    }
+
+    #[test]
+    fn training() {
+        let path = temp_file("noisy_pairs", "tezt,test\nbezt,best\n\nrezt,rest\n");
+
+        let model = ErrorModel::train(&path).unwrap();
+        let untrained = ErrorModel::new();
+
+        // replacing 's' with 'z' has been seen, replacing 's' with 'x' has not
+        assert!(model.likelihood("tezt", "test") > model.likelihood("text", "test"));
+        assert!(model.likelihood("tezt", "test") > untrained.likelihood("tezt", "test"));
+        assert_eq!(untrained.likelihood("tezt", "test"), untrained.likelihood("text", "test"));
+
+        match ErrorModel::train(&temp_file("noisy_bad", "tezt,test\nbezt\n")) {
+            Err(Error::MalformedLine { line, .. }) => assert_eq!(line, 2),
+            _ => panic!("expecting the pair without a correction to be rejected"),
+        }
+    }
+
+    #[test]
+    fn probability() {
+        let mut service = AutoCorrect::new();
+        assert!(service.candidates(String::from("tets")).iter().all(|c| c.probability.is_none()));
+
+        service.set_scorer(Arc::new(NoisyChannel::default()));
+        let results = service.candidates(String::from("tets"));
+
+        let total: f64 = results.iter().map(|c| c.probability.unwrap()).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+
+        for pair in results.windows(2) {
+            assert!(pair[0].probability >= pair[1].probability);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_trace {
+    use super::*;
+    use hashbrown::HashMap;
+    use operation::Operation;
+
+    // replay the operations on the input
+    fn apply(input: &str, operations: &[Operation]) -> String {
+        let runes: Vec<char> = input.chars().collect();
+        let mut inserts: HashMap<usize, Vec<char>> = HashMap::new();
+        let mut replaces: HashMap<usize, Option<char>> = HashMap::new();
+
+        for op in operations {
+            match op {
+                Operation::Insert { pos, rune } => inserts.entry(*pos).or_default().push(*rune),
+                Operation::Delete { pos, .. } => {
+                    replaces.insert(*pos, None);
+                },
+                Operation::Replace { pos, to, .. } => {
+                    replaces.insert(*pos, Some(*to));
+                },
+                Operation::Transpose(first, second) => {
+                    replaces.insert(*first, Some(runes[*second]));
+                    replaces.insert(*second, Some(runes[*first]));
+                },
+            }
+        }
+
+        let mut result = String::new();
+        for pos in 0..=runes.len() {
+            if let Some(inserted) = inserts.get(&pos) {
+                result.extend(inserted.iter());
+            }
+
+            if pos < runes.len() {
+                match replaces.get(&pos) {
+                    Some(Some(rune)) => result.push(*rune),
+                    Some(None) => {},
+                    None => result.push(runes[pos]),
+                }
+            }
+        }
+
+        result
+    }
+
+    #[test]
+    fn operations() {
+        let mut service = AutoCorrect::new();
+        service.set_max_edit(2);
+
+        let results = service.candidates(String::from("tets"));
+        let find = |word: &str| results.iter().find(|c| c.word == word).unwrap();
+
+        assert_eq!(find("test").operations, vec![Operation::Transpose(2, 3)]);
+        assert_eq!(
+            find("gets").operations,
+            vec![Operation::Replace { pos: 0, from: 't', to: 'g' }]
+        );
+
+        for word in ["tets", "ca", "wahtabout"].iter() {
+            for candidate in service.candidates(word.to_string()) {
+                assert_eq!(candidate.operations.len(), candidate.edit as usize);
+                assert_eq!(apply(word, &candidate.operations), candidate.word);
+            }
+        }
+    }
+
+    #[test]
+    fn operations_async() {
+        let service = AutoCorrect::new();
+        let (tx, rx) = mpsc::channel();
+
+        service.candidates_async(String::from("tets"), tx);
+
+        for candidate in rx {
+            assert_eq!(apply("tets", &candidate.operations), candidate.word);
+        }
+    }
+
+    #[test]
+    fn async_on_resized_pool() {
+        let mut service = AutoCorrect::new();
+        service.set_pool_size(1);
+        assert_eq!(service.get_pool_size(), 1);
+
+        let (tx, rx) = mpsc::channel();
+        service.candidates_async(String::from("tets"), tx);
+
+        let found: Vec<Candidate> = rx.iter().collect();
+        assert_eq!(found.len(), service.candidates(String::from("tets")).len());
+    }
+}
+
+#[cfg(test)]
+mod tests_phonetic {
+    use super::*;
+    use test_utils::temp_file;
+    use candidate::MatchKind;
+
+    #[test]
+    fn keys() {
+        let metaphone = Phonetic::DoubleMetaphone;
+        assert_eq!(metaphone.keys("fonetik"), metaphone.keys("phonetic"));
+        assert_eq!(metaphone.keys("nolij"), vec![String::from("NLJ")]);
+        assert_eq!(metaphone.keys("knowledge"), vec![String::from("NLJ")]);
+        assert_eq!(metaphone.keys("thumb"), vec![String::from("0M"), String::from("TM")]);
+
+        let soundex = Phonetic::Soundex;
+        assert_eq!(soundex.keys("robert"), vec![String::from("R163")]);
+        assert_eq!(soundex.keys("rupert"), vec![String::from("R163")]);
+        assert_eq!(soundex.keys("ashcraft"), vec![String::from("A261")]);
+        assert_eq!(soundex.keys("tymczak"), vec![String::from("T522")]);
+        assert!(soundex.keys("").is_empty());
+    }
+
+    #[test]
+    fn candidates() {
+        let mut service = AutoCorrect::new();
+        assert!(service.candidates(String::from("nolij")).is_empty());
+
+        service.set_phonetic(Some(Phonetic::DoubleMetaphone));
+        let results = service.candidates(String::from("nolij"));
+        let knowledge = results.iter().find(|c| c.word == "knowledge").unwrap();
+
+        assert_eq!(knowledge.kind, MatchKind::Phonetic);
+        assert_eq!(knowledge.edit, 6);
+
+        // the edit matches keep their kind, and rank before the phonetic ones
+        let results = service.candidates(String::from("tets"));
+        let test = results.iter().find(|c| c.word == "test").unwrap();
+        assert_eq!(test.kind, MatchKind::Edit);
+
+        let first_phonetic = results.iter().position(|c| c.kind == MatchKind::Phonetic);
+        let last_edit = results.iter().rposition(|c| c.kind == MatchKind::Edit);
+        assert!(first_phonetic.is_none() || first_phonetic > last_edit);
+
+        service.set_phonetic(None);
+        assert!(service.candidates(String::from("nolij")).is_empty());
+    }
+
+    #[test]
+    fn override_dict() {
+        let path = temp_file("phonetic", "phonetic,120\nfanatic,80\nknowledge,300\n");
+
+        let mut config = Config::new_with_params(
+            1, 2, SupportedLocale::EnUs, RunMode::SpaceSensitive, &path
+        );
+        config.set_phonetic(Some(Phonetic::DoubleMetaphone));
+
+        let service = AutoCorrect::try_new_with_config(config).unwrap();
+        let words: Vec<String> = service
+            .candidates(String::from("fonetik"))
+            .into_iter()
+            .map(|c| c.word)
+            .collect();
+
+        // same key, ranked by the edits then the frequency
+        assert_eq!(words, vec![String::from("phonetic"), String::from("fanatic")]);
+    }
+}
+
+#[cfg(test)]
+mod tests_sentence {
+    use super::*;
+    use test_utils::temp_file;
+
+    static COUNTS: &str = "<s> i,100\ni want,50\ni live,10\nwant to,60\nto buy,40\nto by,1\n\
+        buy a,30\nby a,2\nby the,30\nlive by,10\na car,20\nthe sea,10\n";
+
+    static ARPA: &str = "\\data\\\nngram 1=4\nngram 2=2\n\n\\1-grams:\n-1.0\t<s>\t-0.5\n\
+        -0.5\tto\t-0.3\n-1.5\tbuy\n-2.0\t</s>\n\n\\2-grams:\n-0.2\t<s> to\n-0.1\tto buy\n\n\\end\\\n";
+
+    #[test]
+    fn tokenize() {
+        let text = "Hello, don't 'quote' it-self 42";
+        let tokens = common::tokenize(text);
+
+        assert_eq!(tokens, vec![(0, "Hello"), (7, "don't"), (14, "quote"), (21, "it-self")]);
+
+        for (offset, token) in tokens {
+            assert_eq!(&text[offset..offset + token.len()], token);
+        }
+    }
+
+    #[test]
+    fn counts() {
+        let model = LanguageModel::load(&temp_file("lm_counts", COUNTS)).unwrap();
+        assert_eq!(model.order(), 2);
+
+        // 40 out of the 41 bigrams after "to"
+        assert!((model.log_prob(&["to"], "buy") - (40.0f64 / 41.0).log10()).abs() < 1e-9);
+        assert!(model.log_prob(&["to"], "buy") > model.log_prob(&["to"], "by"));
+
+        // unseen bigrams back off to the unigrams, the unknown words least likely of all
+        let backoff = model.log_prob(&["car"], "buy") - model.log_prob(&[], "buy");
+        assert!((backoff - 0.4f64.log10()).abs() < 1e-9);
+        assert!(model.log_prob(&["car"], "zebra") < model.log_prob(&["car"], "buy"));
+    }
+
+    #[test]
+    fn arpa() {
+        let model = LanguageModel::load(&temp_file("lm_arpa", ARPA)).unwrap();
+        assert_eq!(model.order(), 2);
+
+        assert!((model.log_prob(&["to"], "buy") + 0.1).abs() < 1e-9);
+        assert!((model.log_prob(&["<s>"], "buy") + 2.0).abs() < 1e-9);
+        assert!((model.log_prob(&["buy"], "to") + 0.5).abs() < 1e-9);
+        assert!((model.log_prob(&["to"], "car") + 7.3).abs() < 1e-9);
+    }
+
+    #[test]
+    fn malformed() {
+        match LanguageModel::load(&temp_file("lm_bad", "i want,50\ni want\n")) {
+            Err(Error::MalformedLine { line, .. }) => assert_eq!(line, 2),
+            _ => panic!("expected a malformed line error"),
+        }
+    }
+
+    #[test]
+    fn correct_sentence() {
+        let mut service = AutoCorrect::new();
+        assert_eq!(service.correct_sentence("I want to by a car."), "I want to by a car.");
+
+        service.set_language_model(&temp_file("lm_sentence", COUNTS));
+        assert_eq!(service.correct_sentence("I want to by a car."), "I want to buy a car.");
+        assert_eq!(service.correct_sentence("I want to buy a car."), "I want to buy a car.");
+        assert_eq!(service.correct_sentence("I live by the sea"), "I live by the sea");
+        assert_eq!(service.correct_sentence("  "), "  ");
+    }
+
+    #[test]
+    fn known_words_kept() {
+        // the bundled dictionary only lists "dont" and "john", without the apostrophes
+        let sentence = "I don't know, it's John's car.";
+        let mut service = AutoCorrect::new();
+        assert_eq!(service.correct_sentence(sentence), sentence);
+
+        service.set_language_model(&temp_file("lm_known", COUNTS));
+        assert_eq!(service.correct_sentence(sentence), sentence);
+        assert_eq!(service.correct_sentence("I dont want to by a car"), "I dont want to buy a car");
+    }
+}
+
+#[cfg(test)]
+mod tests_confusion {
+    use super::*;
+    use test_utils::temp_file;
+
+    static SETS: &str = "their,there,they're\neffect,affect\n";
+
+    static COUNTS: &str = "over there,20\nthere is,30\nis their,1\ntheir car,15\n\
+        is there,5\nthe effect,30\neffect of,20\nwill affect,20\naffect the,25\nthe car,10\n\
+        car is,10\nis over,5\n";
+
+    fn service() -> AutoCorrect {
+        let mut service = AutoCorrect::new();
+        service.set_confusion_sets(&temp_file("confusion_sets", SETS));
+        service.set_language_model(&temp_file("confusion_lm", COUNTS));
+        service
+    }
+
+    #[test]
+    fn sets() {
+        let mut sets = ConfusionSets::new();
+        sets.add(vec![String::from("to"), String::from("too"), String::from("two")]);
+        sets.add(vec![String::from("to"), String::from("two")]);
+
+        assert_eq!(sets.len(), 2);
+        assert_eq!(sets.alternatives("to"), vec!["too", "two"]);
+        assert_eq!(sets.alternatives("two"), vec!["to", "too"]);
+        assert!(sets.alternatives("three").is_empty());
+    }
+
+    #[test]
+    fn detect() {
+        let service = service();
+        let text = "The car is over their.";
+        let errors = service.real_word_errors(text);
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].offset, 16);
+        assert_eq!(&text[errors[0].offset..errors[0].offset + 5], "their");
+        assert_eq!(errors[0].suggestion, "there");
+        assert!(errors[0].confidence > 0.5 && errors[0].confidence <= 1.0);
+
+        let errors = service.real_word_errors("It will effect the car");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].word, "effect");
+        assert_eq!(errors[0].suggestion, "affect");
+
+        assert!(service.real_word_errors("Their car is over there.").is_empty());
+        assert!(service.real_word_errors("The effect of it").is_empty());
+    }
+
+    #[test]
+    fn unconfigured() {
+        let service = AutoCorrect::new();
+        assert!(service.real_word_errors("The car is over their.").is_empty());
+
+        match ConfusionSets::load(&temp_file("confusion_bad", "their,there\neffect\n")) {
+            Err(Error::MalformedLine { line, .. }) => assert_eq!(line, 2),
+            _ => panic!("expected a malformed line error"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_decision {
+    use super::*;
+
+    fn words(decision: &Decision) -> Vec<String> {
+        match decision {
+            Decision::Keep => Vec::new(),
+            Decision::Replace(candidate) => vec![candidate.word.to_owned()],
+            Decision::Suggest(candidates) => candidates.iter().map(|c| c.word.to_owned()).collect(),
+        }
+    }
+
+    #[test]
+    fn decide() {
+        let service = AutoCorrect::new();
+
+        assert_eq!(service.autocorrect(String::from("the")), Decision::Keep);
+        assert_eq!(service.autocorrect(String::from("zzzzzzzz")), Decision::Keep);
+
+        match service.autocorrect(String::from("teh")) {
+            Decision::Replace(candidate) => assert_eq!(candidate.word, "the"),
+            other => panic!("expected a replace, got {:?}", words(&other)),
+        }
+
+        // too short to replace, however confident
+        match service.autocorrect(String::from("ot")) {
+            Decision::Suggest(candidates) => {
+                assert_eq!(candidates.len(), 3);
+                assert_eq!(candidates[0].word, "to");
+            },
+            other => panic!("expected suggestions, got {:?}", words(&other)),
+        }
+
+        // no candidate clearly leads
+        match service.autocorrect(String::from("tets")) {
+            Decision::Suggest(candidates) => assert_eq!(candidates.len(), 3),
+            other => panic!("expected suggestions, got {:?}", words(&other)),
+        }
+    }
+
+    #[test]
+    fn policy() {
+        let mut service = AutoCorrect::new();
+        service.set_decision_policy(DecisionPolicy { min_length: 4, ..DecisionPolicy::default() });
+        match service.autocorrect(String::from("teh")) {
+            Decision::Suggest(candidates) => assert_eq!(candidates[0].word, "the"),
+            other => panic!("expected suggestions, got {:?}", words(&other)),
+        }
+
+        // a frequent enough input is kept
+        assert!(service.autocorrect(String::from("ot")) != Decision::Keep);
+        service.set_decision_policy(DecisionPolicy { keep_frequency: 1000, ..DecisionPolicy::default() });
+        assert_eq!(service.autocorrect(String::from("ot")), Decision::Keep);
+
+        service.set_decision_policy(DecisionPolicy { margin: 1.0, ..DecisionPolicy::default() });
+        match service.autocorrect(String::from("teh")) {
+            Decision::Suggest(candidates) => assert_eq!(candidates[0].word, "the"),
+            other => panic!("expected suggestions, got {:?}", words(&other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_top_k {
+    use super::*;
+    use test_utils::{summary, top_words_config};
+
+    #[test]
+    fn same_as_full() {
+        let modes = [
+            RunMode::SpaceSensitive,
+            RunMode::SpeedSensitive,
+            RunMode::SymmetricDelete,
+            RunMode::Automaton,
+            RunMode::BkTree,
+        ];
+
+        for mode in modes.iter() {
+            let config = top_words_config("top_k", *mode, 2);
+            let service = AutoCorrect::try_new_with_config(config).unwrap();
+
+            for word in ["tets", "the", "speling", "wahtabout", "xyzzyq"].iter() {
+                let full = service.candidates(word.to_string());
+
+                for k in [1, 3, 10].iter() {
+                    let top = service.candidates_top_k(word.to_string(), *k);
+                    let expected = &full[..(*k).min(full.len())];
+
+                    assert_eq!(summary(&top), summary(expected), "{} top {}", word, k);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn custom_scorer() {
+        let mut service = AutoCorrect::new();
+        service.set_max_edit(2);
+        service.set_scorer(Arc::new(|_input: &str, candidate: &Candidate| f64::from(candidate.edit)));
+
+        let full = service.candidates(String::from("tets"));
+        let top = service.candidates_top_k(String::from("tets"), 3);
+
+        assert_eq!(top.iter().map(|c| c.edit).collect::<Vec<u8>>(), vec![2, 2, 2]);
+        assert_eq!(summary(&top), summary(&full[..3]));
+        assert!(service.candidates_top_k(String::from("tets"), 0).is_empty());
+    }
+}
+
+#[cfg(test)]
+mod tests_text {
+    use super::*;
+    use text::{apply, Fix};
+
+    #[test]
+    fn check_text() {
+        let service = AutoCorrect::new();
+        let text = "Teh  cat (hat) is \"hapy\", right?\nNaïve tets—done.";
+        let issues = service.check_text(text);
+
+        let tokens: Vec<&str> = issues.iter().map(|issue| issue.token.as_str()).collect();
+        assert_eq!(tokens, vec!["Teh", "hapy", "tets"]);
+
+        for issue in issues.iter() {
+            assert_eq!(&text[issue.span.clone()], issue.token);
+        }
+
+        assert_eq!(issues[0].span, 0..3);
+        assert_eq!(issues[0].suggestions[0].word, "The");
+        assert_eq!(issues[1].suggestions[0].word, "happy");
+
+        let fixes: Vec<Fix> = issues.iter().take(2).filter_map(|issue| issue.fix()).collect();
+        assert_eq!(
+            apply(text, &fixes),
+            "The  cat (hat) is \"happy\", right?\nNaïve tets—done."
+        );
+
+        assert!(service.check_text("").is_empty());
+        assert!(service.check_text("All fine here.").is_empty());
+    }
+
+    #[test]
+    fn apostrophes() {
+        // the bundled dictionary lists "dont" and "its", but neither "don't" nor "it's"
+        let service = AutoCorrect::new();
+        assert!(service.check_text("I don't know, it's John's car, isn't it?").is_empty());
+        assert_eq!(service.autocorrect(String::from("don't")), Decision::Keep);
+
+        let issues = service.check_text("It's Jonh's");
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].token, "Jonh's");
+    }
+
+    #[test]
+    fn apply_fixes() {
+        let text = "a teh, b";
+
+        // in any order, keeping the text around the spans
+        let fixes = vec![Fix::new(7..8, "bee"), Fix::new(2..5, "the")];
+        assert_eq!(apply(text, &fixes), "a the, bee");
+
+        // the overlapping and invalid fixes are skipped
+        let fixes = vec![
+            Fix::new(2..5, "the"),
+            Fix::new(3..6, "xx"),
+            Fix::new(7..20, "bee"),
+            Fix::new(0..1, ""),
+        ];
+        assert_eq!(apply(text, &fixes), " the, b");
+        assert_eq!(apply("naïve", &[Fix::new(2..3, "i")]), "naïve");
+        assert_eq!(apply(text, &[]), text);
+    }
+}
+
+#[cfg(test)]
+mod tests_case {
+    use super::*;
+    use test_utils::{first, temp_file};
+    use case::{self, Case};
+
+    #[test]
+    fn patterns() {
+        assert_eq!(Case::of("teh"), Case::Lower);
+        assert_eq!(Case::of("Teh"), Case::Title);
+        assert_eq!(Case::of("I"), Case::Title);
+        assert_eq!(Case::of("TEH"), Case::Upper);
+        assert_eq!(Case::of("DON'T"), Case::Upper);
+        assert_eq!(Case::of("iPhoen"), Case::Mixed(vec![false, true, false, false, false, false]));
+
+        assert_eq!(Case::of("Teh").apply("the"), "The");
+        assert_eq!(Case::of("TEH").apply("the"), "THE");
+        assert_eq!(Case::of("iPhoen").apply("iphones"), "iPhones");
+        assert_eq!(Case::of("Straße").apply("strasse"), "Strasse");
+        assert_eq!(Case::of("ÉTÉ").apply("été"), "ÉTÉ");
+
+        assert_eq!(case::recase("nasaa", "nasa", Some("NASA")), "NASA");
+        assert_eq!(case::recase("LONDN", "london", Some("London")), "LONDON");
+    }
+
+    #[test]
+    fn candidates() {
+        let service = AutoCorrect::new();
+
+        assert_eq!(first(&service, "teh"), "the");
+        assert_eq!(first(&service, "Teh"), "The");
+        assert_eq!(first(&service, "TEH"), "THE");
+        assert_eq!(first(&service, "  Teh "), "The");
+
+        let top = service.candidates_top_k(String::from("Teh"), 1);
+        assert_eq!(top[0].word, "The");
+
+        let (tx, rx) = mpsc::channel();
+        service.candidates_async(String::from("TEH"), tx);
+        assert!(rx.iter().all(|candidate| candidate.word == candidate.word.to_uppercase()));
+    }
+
+    #[test]
+    fn canonical() {
+        let content = "iPhone,100\nNASA,80\nLondon,60\nlondon,10\nLondon,50\nphone,90\n";
+        let path = temp_file("case", content);
+
+        let config = Config::new_with_params(
+            1, 2, SupportedLocale::EnUs, RunMode::SpaceSensitive, &path
+        );
+        let service = AutoCorrect::try_new_with_config(config).unwrap();
+
+        assert_eq!(first(&service, "iphone"), "iPhone");
+        assert_eq!(first(&service, "Iphon"), "iPhone");
+        assert_eq!(first(&service, "IPHONEE"), "IPHONE");
+        assert_eq!(first(&service, "nasaa"), "NASA");
+        assert_eq!(first(&service, "Phon"), "Phone");
+
+        // the last entry of a word sets both its score and its spelling
+        let london = service.candidates(String::from("londn"));
+        assert_eq!(london[0].word, "London");
+        assert_eq!(london[0].score, 50);
+
+        assert_eq!(service.autocorrect(String::from("nasa")), Decision::Keep);
+        assert!(service.check_text("NASA: iPhone, London!").is_empty());
+    }
+}
+
+#[cfg(test)]
+mod tests_split {
+    use super::*;
+    use test_utils::{summary, temp_file};
+    use candidate::MatchKind;
+    use operation::Operation;
+    use text::{apply, Fix};
+
+    #[test]
+    fn split() {
+        let mut service = AutoCorrect::new();
+        assert!(service.candidates(String::from("thecar")).iter().all(|c| c.kind == MatchKind::Edit));
+
+        service.set_split_words(true);
+
+        let results = service.candidates(String::from("thecar"));
+        let split = results.iter().find(|c| c.word == "the car").unwrap();
+
+        assert_eq!(split.kind, MatchKind::Split);
+        assert_eq!(split.edit, 1);
+        assert_eq!(split.operations, vec![Operation::Insert { pos: 3, rune: ' ' }]);
+
+        // the splits are ranked by the combined frequency of their parts
+        let results = service.candidates(String::from("Inthe"));
+        let splits: Vec<&str> = results
+            .iter()
+            .filter(|c| c.kind == MatchKind::Split)
+            .map(|c| c.word.as_str())
+            .collect();
+
+        assert_eq!(splits[0], "In the");
+        assert!(splits.len() > 1);
+
+        // a space for each edit
+        service.set_max_edit(2);
+        let results = service.candidates(String::from("whatabouthim"));
+        let split = results.iter().find(|c| c.word == "what about him").unwrap();
+        assert_eq!(split.edit, 2);
+        assert!(results.iter().all(|c| c.word.split(' ').count() <= 3));
+        assert_eq!(
+            summary(&service.candidates_top_k(String::from("whatabouthim"), 2)),
+            summary(&results[..results.len().min(2)])
+        );
+    }
+
+    #[test]
+    fn join() {
+        let mut service = AutoCorrect::new();
+        let text = "Some thing is in the box, a cross the street.";
+        assert!(service.check_text(text).is_empty());
+
+        service.set_join_words(true);
+        let issues = service.check_text(text);
+
+        let tokens: Vec<&str> = issues.iter().map(|issue| issue.token.as_str()).collect();
+        assert_eq!(tokens, vec!["Some thing", "a cross"]);
+
+        let joined = &issues[0].suggestions[0];
+        assert_eq!(joined.word, "Something");
+        assert_eq!(joined.kind, MatchKind::Join);
+        assert_eq!(joined.edit, 1);
+        assert_eq!(issues[0].span, 0..10);
+
+        let fixes: Vec<Fix> = issues.iter().filter_map(|issue| issue.fix()).collect();
+        assert_eq!(apply(text, &fixes), "Something is in the box, across the street.");
+
+        // only the spaces may stand between the joined words
+        assert!(service.check_text("some, thing").is_empty());
+    }
+
+    #[test]
+    fn total_of_duplicates() {
+        // the last entry of a word replaces its score, in the total the splits are scored by
+        let path = temp_file("total_of_duplicates", "the,50\ncar,30\nthe,70\nThe,100\n");
+        let config = Config::new_with_params(1, 2, SupportedLocale::EnUs, RunMode::SpaceSensitive, &path);
+        let service = AutoCorrect::try_new_with_config(config).unwrap();
+
+        assert_eq!(service.dict.total(), 130);
+        assert_eq!(service.dict.check("the"), Some(100));
+    }
+}
+
+#[cfg(test)]
+mod tests_unicode {
+    use super::*;
+    use test_utils::{first, temp_file};
+    use common;
+    use std::fs;
+    use support;
+
+    #[test]
+    fn generators() {
+        let variations = common::edits1("café", SupportedLocale::EnUs);
+        assert!(variations.contains(&String::from("cafe")));
+        assert!(variations.contains(&String::from("caf")));
+        assert!(variations.contains(&String::from("caéf")));
+
+        let variations = common::edits1("dont", SupportedLocale::EnUs);
+        assert!(variations.contains(&String::from("don't")));
+
+        for rune in "az'-éïß日".chars() {
+            assert!(support::get_char_code(rune) < 32);
+        }
+    }
+
+    #[test]
+    fn hyphens() {
+        let text = "A well-known x-ray: pro - con, pro--con, -ish";
+        let tokens: Vec<&str> = common::tokenize(text).into_iter().map(|(_, token)| token).collect();
+        assert_eq!(tokens, vec!["A", "well-known", "x-ray", "pro", "con", "pro", "con", "ish"]);
+
+        let path = temp_file("hyphens", "x-ray,30\ncafé,90\nnaïve,100\n");
+        let config = Config::new_with_params(
+            1, 2, SupportedLocale::EnUs, RunMode::SpaceSensitive, &path
+        );
+        let service = AutoCorrect::try_new_with_config(config).unwrap();
+
+        // the words joined by hyphens are known if each of them is
+        assert!(service.check_text("x-ray café-naïve").is_empty());
+
+        let issues = service.check_text("café x-rya");
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].token, "x-rya");
+        assert_eq!(issues[0].span, 6..11);
+        assert_eq!(issues[0].suggestions[0].word, "x-ray");
+    }
+
+    #[test]
+    fn candidates() {
+        let content = "don't,300\nrésumé,200\nresume,50\nnaïve,100\ncafé,90\nx-ray,30\n";
+        let path = temp_file("unicode", content);
+
+        let modes = [
+            RunMode::SpeedSensitive,
+            RunMode::SpaceSensitive,
+            RunMode::SymmetricDelete,
+            RunMode::Automaton,
+            RunMode::BkTree,
+        ];
+
+        for mode in modes.iter() {
+            let config = Config::new_with_params(1, 2, SupportedLocale::EnUs, *mode, &path);
+            let service = AutoCorrect::try_new_with_config(config).unwrap();
+
+            assert_eq!(first(&service, "dont"), "don't");
+            assert_eq!(first(&service, "Dont"), "Don't");
+            assert_eq!(first(&service, "résume"), "résumé");
+            assert_eq!(first(&service, "naive"), "naïve");
+            assert_eq!(first(&service, "CAFE"), "CAFÉ");
+            assert_eq!(first(&service, "xray"), "x-ray");
+
+            // the runes sharing a bit of the quick reject are still told apart
+            assert!(service.candidates(String::from("cafë")).iter().all(|c| c.word != "cafë"));
+
+            let issues = service.check_text("Don't, naive café!");
+            assert_eq!(issues.len(), 1);
+            assert_eq!(issues[0].token, "naive");
+            assert_eq!(issues[0].span, 7..12);
+        }
+
+        let _ = fs::remove_file(&path);
+    }
+}
+
+#[cfg(test)]
+mod tests_locale {
+    use super::*;
+    use test_utils::{first, temp_file};
+    use std::fs;
+
+    // the locales without a bundled dictionary are tested against a few words of their own
+    static WORDS_DE: &str = "der,900\nist,800\nim,700\nhaus,500\nhand,400\ngarten,300\nstraße,200\nfenster,200\nschlüssel,100\ntür,100\nschuh,100\nhandschuh,50";
+    static WORDS_FR: &str = "école,300\nécoles,100\ncole,10";
+    static WORDS_ES: &str = "mañana,300\nmanta,100";
+    static WORDS_IT: &str = "città,300\ncittadino,100";
+
+    fn service(locale: SupportedLocale, mode: RunMode) -> AutoCorrect {
+        let config = Config::new_with_params(1, 2, locale, mode, "");
+        AutoCorrect::try_new_with_config(config).unwrap()
+    }
+
+    fn supplied(locale: SupportedLocale, mode: RunMode, words: &str) -> (AutoCorrect, String) {
+        let path = temp_file("locale", words);
+        let config = Config::new_with_params(1, 2, locale, mode, &path);
+
+        (AutoCorrect::try_new_with_config(config).unwrap(), path)
+    }
+
+    #[test]
+    fn dictionaries() {
+        for mode in [RunMode::SpaceSensitive, RunMode::SpeedSensitive].iter() {
+            assert_eq!(first(&service(SupportedLocale::EnUs, *mode), "colur"), "color");
+            assert_eq!(first(&service(SupportedLocale::EnGb, *mode), "colur"), "colour");
+            assert_eq!(first(&service(SupportedLocale::EnGb, *mode), "realise"), "realise");
+        }
+
+        // the American spellings aren't British words
+        let issues = service(SupportedLocale::EnGb, RunMode::SpaceSensitive).check_text("color");
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].suggestions[0].word, "colour");
+        assert!(service(SupportedLocale::EnUs, RunMode::SpaceSensitive).check_text("color").is_empty());
+    }
+
+    #[test]
+    fn supplied_dictionaries() {
+        for mode in [RunMode::SpaceSensitive, RunMode::SpeedSensitive].iter() {
+            let cases = [
+                (SupportedLocale::De, WORDS_DE, "strase", "straße"),
+                (SupportedLocale::De, WORDS_DE, "Fnster", "Fenster"),
+                (SupportedLocale::Fr, WORDS_FR, "ecole", "école"),
+                (SupportedLocale::Es, WORDS_ES, "Manana", "Mañana"),
+                (SupportedLocale::It, WORDS_IT, "citta", "città"),
+            ];
+
+            for (locale, words, input, expected) in cases.iter() {
+                let (service, path) = supplied(*locale, *mode, words);
+                assert_eq!(first(&service, input), *expected);
+                fs::remove_file(path).unwrap();
+            }
+        }
+
+        // no dictionary is bundled for these, so one must be supplied
+        let config = Config::new_with_params(1, 2, SupportedLocale::Fr, RunMode::SpaceSensitive, "");
+        match AutoCorrect::try_new_with_config(config) {
+            Err(Error::InvalidConfig(reason)) => assert!(reason.contains("override_dict")),
+            _ => panic!("expected an invalid config"),
+        }
+    }
+
+    #[test]
+    fn set_locale() {
+        let mut service = AutoCorrect::new();
+        assert_eq!(first(&service, "colur"), "color");
+
+        service.set_locale(SupportedLocale::EnGb);
+        assert_eq!(first(&service, "colur"), "colour");
+
+        service.set_locale(SupportedLocale::EnUs);
+        assert_eq!(first(&service, "Colur"), "Color");
+
+        // without a dictionary to load, the former locale stays
+        service.set_locale(SupportedLocale::De);
+        assert!(service.get_locale() == SupportedLocale::EnUs);
+        assert_eq!(first(&service, "colur"), "color");
+    }
+
+    #[test]
+    fn compounds() {
+        let mut service = service(SupportedLocale::EnUs, RunMode::SpaceSensitive);
+        assert!(!service.get_join_words() && !service.get_compound_words());
+        assert!(!service.get_split_words());
+        assert_eq!(service.check_text("The doorhandle").len(), 1);
+
+        service.set_compound_words(true);
+        service.set_join_words(true);
+
+        assert!(service.check_text("The doorhandle is in the hall.").is_empty());
+        assert_eq!(service.autocorrect(String::from("keyring")), Decision::Keep);
+
+        let issues = service.check_text("The hand bag");
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].suggestions[0].word, "handbag");
+
+        service.set_compound_words(false);
+        assert_eq!(service.check_text("The doorhandle").len(), 1);
+    }
+
+    #[test]
+    fn german_compounds() {
+        let (mut service, path) = supplied(SupportedLocale::De, RunMode::SpaceSensitive, WORDS_DE);
+        assert!(service.get_join_words() && service.get_compound_words());
+        assert!(!service.get_split_words());
+
+        assert!(service.check_text("Der Hausschlüssel ist im Garten.").is_empty());
+        assert_eq!(service.autocorrect(String::from("Haustür")), Decision::Keep);
+
+        let issues = service.check_text("Der Hand schuh");
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].suggestions[0].word, "Handschuh");
+
+        // the toggles set on the config win over the locale's
+        service.set_compound_words(false);
+        assert_eq!(service.check_text("Der Hausschlüssel").len(), 1);
+
+        fs::remove_file(path).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests_zh_cn {
+    use super::*;
+    use test_utils::temp_file;
+    use candidate::MatchKind;
+    use std::fs;
+
+    fn words(candidates: &[Candidate]) -> Vec<&str> {
+        candidates.iter().map(|candidate| candidate.word.as_str()).collect()
+    }
+
+    #[test]
+    fn bundled() {
+        let config = Config::new_with_params(1, 2, SupportedLocale::ZhCn, RunMode::SpaceSensitive, "");
+        let service = AutoCorrect::try_new_with_config(config).unwrap();
+
+        // typed in pinyin
+        let results = service.candidates(String::from("zhongguo"));
+        assert_eq!(results[0].word, "中国");
+        assert_eq!((results[0].kind, results[0].edit), (MatchKind::Pinyin, 0));
+        assert!(results[0].operations.is_empty());
+
+        let results = service.candidates(String::from("zhonggou"));
+        assert!(results.iter().any(|c| c.word == "中国" && c.edit == 1));
+        assert!(words(&service.candidates(String::from("zai jian"))).contains(&"再见"));
+
+        // typed with a character sounding alike
+        let results = service.candidates(String::from("以经"));
+        assert_eq!(results[0].word, "已经");
+        assert_eq!(results[0].kind, MatchKind::Phonetic);
+        assert!(words(&service.candidates(String::from("在见"))).contains(&"再见"));
+
+        assert_eq!(service.candidates(String::from("中国"))[0].kind, MatchKind::Edit);
+
+        // only the Chinese words of a text are checked
+        let issues = service.check_text("Hello 以经");
+        let tokens: Vec<&str> = issues.iter().map(|issue| issue.token.as_str()).collect();
+        assert_eq!(tokens, vec!["以经"]);
+        assert_eq!(issues[0].suggestions[0].word, "已经");
+
+        // ranked by the frequencies of the dictionary within the same edits
+        let results = service.candidates(String::from("beijing"));
+        let exact: Vec<u32> = results.iter().filter(|c| c.edit == 0).map(|c| c.score).collect();
+        assert!(exact.windows(2).all(|pair| pair[0] >= pair[1]));
+        assert_eq!(results[0].word, "北京");
+    }
+
+    #[test]
+    fn override_dict() {
+        let path = temp_file("zh_cn", "你好 300\n您好 100\n年号 5\n线 400\n西安 50\n");
+
+        let config = Config::new_with_params(
+            1, 2, SupportedLocale::ZhCn, RunMode::SymmetricDelete, &path
+        );
+        let service = AutoCorrect::try_new_with_config(config).unwrap();
+
+        assert_eq!(words(&service.candidates(String::from("nihao"))), vec!["你好", "您好"]);
+        assert_eq!(words(&service.candidates(String::from("ninhao"))), vec!["您好", "你好", "年号"]);
+        assert_eq!(words(&service.candidates(String::from("你号"))), vec!["你好"]);
+
+        // "l" sounds like "n", and the back nasals like the front ones
+        assert_eq!(words(&service.candidates(String::from("李好"))), vec!["你好"]);
+        assert_eq!(words(&service.candidates(String::from("宁好"))), vec!["您好"]);
+
+        // the separators typed between the syllables tell the words spelled alike apart
+        assert_eq!(words(&service.candidates(String::from("xian"))), vec!["线", "西安"]);
+        assert_eq!(words(&service.candidates(String::from("xi'an"))), vec!["西安", "线"]);
+        assert_eq!(words(&service.candidates(String::from("xi an"))), vec!["西安", "线"]);
+
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,143 @@
+use common;
+use error::Error;
+use keyboard::Keyboard;
+
+/// A distance between words. The `BkTree` prunes its search with the triangle inequality, so a
+/// metric used there must be symmetric, zero only between identical words, and satisfy
+/// `distance(a, c) <= distance(a, b) + distance(b, c)`.
+pub trait Metric {
+    fn distance(&self, source: &str, target: &str) -> u32;
+}
+
+impl<F> Metric for F where F: Fn(&str, &str) -> u32 {
+    fn distance(&self, source: &str, target: &str) -> u32 {
+        self(source, target)
+    }
+}
+
+/// The number of inserts, deletes and replaces that turns one word into the other.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Levenshtein;
+
+impl Metric for Levenshtein {
+    fn distance(&self, source: &str, target: &str) -> u32 {
+        let target: Vec<char> = target.chars().collect();
+        let mut prev: Vec<usize> = (0..=target.len()).collect();
+
+        for (i, rune) in source.chars().enumerate() {
+            let mut row = Vec::with_capacity(prev.len());
+            row.push(i + 1);
+
+            for (j, other) in target.iter().enumerate() {
+                let cost = if rune == *other { 0 } else { 1 };
+                row.push((prev[j] + cost).min(prev[j + 1] + 1).min(row[j] + 1));
+            }
+
+            prev = row;
+        }
+
+        prev[target.len()] as u32
+    }
+}
+
+/// Same as `Levenshtein`, plus the transposes of adjacent characters, which is the distance the
+/// `AutoCorrect` service reports in `Candidate::edit`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Damerau;
+
+impl Metric for Damerau {
+    fn distance(&self, source: &str, target: &str) -> u32 {
+        common::damerau_distance(source, target) as u32
+    }
+}
+
+/// A weighted `Levenshtein` distance, where replacing a character with one from a neighboring
+/// key costs `near`, with any other character `far`, and inserting or deleting one `indel`.
+///
+/// The weights must keep it a metric, as the `BkTree` relies on the triangle inequality:
+/// - `near` and `indel` are positive, so only the same words are 0 apart;
+/// - `near <= far`, so the neighboring keys are the closer ones;
+/// - `far <= 2 * near`, so replacing with a distant key never costs more than going through a
+///   key neighboring both;
+/// - `far <= 2 * indel`, so replacing never costs more than deleting the character and
+///   inserting the other one.
+#[derive(Debug, Clone)]
+pub struct KeyboardWeighted {
+    keyboard: Keyboard,
+    near: u32,
+    far: u32,
+    indel: u32,
+}
+
+impl KeyboardWeighted {
+    /// Weigh the replaces on the `keyboard`, or fail with an `InvalidConfig` error if the
+    /// weights break any of the conditions above.
+    pub fn new(keyboard: Keyboard, near: u32, far: u32, indel: u32) -> Result<Self, Error> {
+        let broken = if near == 0 || indel == 0 {
+            Some("the near and indel costs must be greater than 0")
+        } else if near > far {
+            Some("the near cost must not exceed the far cost")
+        } else if far > 2 * near {
+            Some("the far cost must not exceed twice the near cost")
+        } else if far > 2 * indel {
+            Some("the far cost must not exceed twice the indel cost")
+        } else {
+            None
+        };
+
+        match broken {
+            Some(reason) => Err(Error::InvalidConfig(String::from(reason))),
+            None => Ok(KeyboardWeighted { keyboard, near, far, indel }),
+        }
+    }
+
+    /// Weigh the replaces on the QWERTY layout.
+    pub fn qwerty(near: u32, far: u32, indel: u32) -> Result<Self, Error> {
+        KeyboardWeighted::new(Keyboard::qwerty(), near, far, indel)
+    }
+
+    fn replace_cost(&self, a: char, b: char) -> u32 {
+        if a == b {
+            0
+        } else if self.keyboard.is_near(a, b) {
+            self.near
+        } else {
+            self.far
+        }
+    }
+}
+
+impl Default for KeyboardWeighted {
+    fn default() -> Self {
+        KeyboardWeighted {
+            keyboard: Keyboard::qwerty(),
+            near: 1,
+            far: 2,
+            indel: 2,
+        }
+    }
+}
+
+impl Metric for KeyboardWeighted {
+    fn distance(&self, source: &str, target: &str) -> u32 {
+        let target: Vec<char> = target.chars().collect();
+        let mut prev: Vec<u32> = (0..=target.len() as u32).map(|j| j * self.indel).collect();
+
+        for (i, rune) in source.chars().enumerate() {
+            let mut row = Vec::with_capacity(prev.len());
+            row.push((i as u32 + 1) * self.indel);
+
+            for (j, other) in target.iter().enumerate() {
+                let dist = (prev[j] + self.replace_cost(rune, *other))
+                    .min(prev[j + 1] + self.indel)
+                    .min(row[j] + self.indel);
+
+                row.push(dist);
+            }
+
+            prev = row;
+        }
+
+        prev[target.len()]
+    }
+}
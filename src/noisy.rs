@@ -0,0 +1,172 @@
+use std::io::BufRead;
+
+use hashbrown::{HashMap, HashSet};
+
+use candidate::Candidate;
+use common;
+use error::Error;
+use operation::{self, Operation};
+use scorer::Scorer;
+
+// the character before the start of a word, to condition the edits on the first character
+static WORD_START: char = '^';
+
+// the chance that a word is typed without any typo
+static NO_TYPO: f64 = 0.95;
+
+// the least alphabet size for the add-one smoothing, so an untrained model isn't overconfident
+static MIN_ALPHABET: usize = 26;
+
+/// The error model P(typo | word) of the noisy channel, from the confusion matrices of the
+/// single character edits: the count of each edit seen in the training pairs, over the count of
+/// the characters (or character pairs) it applies to. The counts are add-one smoothed, so the
+/// untrained model gives every edit the same chance.
+#[derive(Debug, Clone, Default)]
+pub struct ErrorModel {
+    insertions: HashMap<(char, char), u32>,
+    deletions: HashMap<(char, char), u32>,
+    substitutions: HashMap<(char, char), u32>,
+    transpositions: HashMap<(char, char), u32>,
+    unigrams: HashMap<char, u32>,
+    bigrams: HashMap<(char, char), u32>,
+    alphabet: HashSet<char>,
+}
+
+impl ErrorModel {
+    /// The untrained model.
+    pub fn new() -> Self {
+        ErrorModel::default()
+    }
+
+    /// Train the model from the file at `path`, where each line is a misspelling and its
+    /// correction, formatted as `misspelling,correction`.
+    pub fn train(path: &str) -> Result<Self, Error> {
+        let reader = common::open_file(path)?;
+        let mut pairs = Vec::new();
+
+        for (idx, line) in reader.lines().enumerate() {
+            let line = line.map_err(|err| Error::io(path, err))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let temp: Vec<&str> = line.splitn(2, common::DELIM).map(|part| part.trim()).collect();
+            match (temp.first(), temp.get(1)) {
+                (Some(typo), Some(word)) if !typo.is_empty() && !word.is_empty() => {
+                    pairs.push((typo.to_lowercase(), word.to_lowercase()));
+                },
+                _ => {
+                    return Err(Error::MalformedLine { line: idx + 1, content: line.to_owned() });
+                },
+            }
+        }
+
+        Ok(ErrorModel::from_pairs(pairs))
+    }
+
+    /// Train the model from the `(misspelling, correction)` pairs.
+    pub fn from_pairs<I>(pairs: I) -> Self
+        where I: IntoIterator<Item = (String, String)>
+    {
+        let mut model = ErrorModel::new();
+        for (typo, word) in pairs {
+            model.learn(&typo, &word);
+        }
+
+        model
+    }
+
+    /// The chance of typing the `typo` when meaning the `word`.
+    pub fn likelihood(&self, typo: &str, word: &str) -> f64 {
+        let word_runes: Vec<char> = word.chars().collect();
+        let ops = operation::operations(word, typo);
+
+        if ops.is_empty() {
+            return NO_TYPO;
+        }
+
+        let alphabet = self.alphabet.len().max(MIN_ALPHABET) as f64;
+        let chance = |count: Option<&u32>, total: Option<&u32>| {
+            let count = f64::from(count.cloned().unwrap_or(0));
+            let total = f64::from(total.cloned().unwrap_or(0));
+
+            (count + 1.0) / (total + alphabet)
+        };
+
+        ops.iter().fold(1.0 - NO_TYPO, |acc, op| {
+            let prev = |pos: usize| if pos == 0 { WORD_START } else { word_runes[pos - 1] };
+
+            acc * match op {
+                Operation::Insert { pos, rune } => {
+                    let key = (prev(*pos), *rune);
+                    chance(self.insertions.get(&key), self.unigrams.get(&key.0))
+                },
+                Operation::Delete { pos, rune } => {
+                    let key = (prev(*pos), *rune);
+                    chance(self.deletions.get(&key), self.bigrams.get(&key))
+                },
+                Operation::Replace { from, to, .. } => {
+                    chance(self.substitutions.get(&(*from, *to)), self.unigrams.get(from))
+                },
+                Operation::Transpose(first, second) => {
+                    let key = (word_runes[*first], word_runes[*second]);
+                    chance(self.transpositions.get(&key), self.bigrams.get(&key))
+                },
+            }
+        })
+    }
+
+    fn learn(&mut self, typo: &str, word: &str) {
+        let word_runes: Vec<char> = word.chars().collect();
+        let mut prev = WORD_START;
+        *self.unigrams.entry(WORD_START).or_insert(0) += 1;
+
+        for rune in word_runes.iter() {
+            *self.unigrams.entry(*rune).or_insert(0) += 1;
+            *self.bigrams.entry((prev, *rune)).or_insert(0) += 1;
+            prev = *rune;
+        }
+
+        self.alphabet.extend(word.chars().chain(typo.chars()));
+
+        let prev = |pos: usize| if pos == 0 { WORD_START } else { word_runes[pos - 1] };
+
+        for op in operation::operations(word, typo) {
+            let (matrix, key) = match op {
+                Operation::Insert { pos, rune } => (&mut self.insertions, (prev(pos), rune)),
+                Operation::Delete { pos, rune } => (&mut self.deletions, (prev(pos), rune)),
+                Operation::Replace { from, to, .. } => (&mut self.substitutions, (from, to)),
+                Operation::Transpose(first, second) => {
+                    (&mut self.transpositions, (word_runes[first], word_runes[second]))
+                },
+            };
+
+            *matrix.entry(key).or_insert(0) += 1;
+        }
+    }
+}
+
+/// Rank by the chance that the candidate is the word meant by the input: the word prior from
+/// its frequency, times the error model. Sets `Candidate::probability`, normalized over all
+/// the candidates of the query.
+#[derive(Debug, Clone, Default)]
+pub struct NoisyChannel {
+    model: ErrorModel,
+}
+
+impl NoisyChannel {
+    pub fn new(model: ErrorModel) -> Self {
+        NoisyChannel { model }
+    }
+}
+
+impl Scorer for NoisyChannel {
+    fn rank(&self, input: &str, candidate: &Candidate) -> f64 {
+        self.probability(input, candidate).unwrap_or(0.0)
+    }
+
+    fn probability(&self, input: &str, candidate: &Candidate) -> Option<f64> {
+        let prior = f64::from(candidate.score) + 1.0;
+        Some(prior * self.model.likelihood(input, &candidate.word))
+    }
+}
@@ -0,0 +1,127 @@
+use hashbrown::HashMap;
+
+/// A single edit on the characters of a word, positioned by the character indexes in the word
+/// before any edit is applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// Insert the `rune` before the character at `pos`, or at the end if `pos` is the length.
+    Insert { pos: usize, rune: char },
+    Delete { pos: usize, rune: char },
+    Replace { pos: usize, from: char, to: char },
+    /// Swap the characters at the 2 positions. Any characters in between are deleted, and the
+    /// inserted ones follow as separate operations.
+    Transpose(usize, usize),
+}
+
+impl Operation {
+    fn position(&self) -> usize {
+        match self {
+            Operation::Insert { pos, .. }
+            | Operation::Delete { pos, .. }
+            | Operation::Replace { pos, .. } => *pos,
+            Operation::Transpose(pos, _) => *pos,
+        }
+    }
+}
+
+/// The fewest operations turning the `source` into the `target`, ordered by their positions. The
+/// number of operations is always the `common::damerau_distance` between the 2 words.
+pub(crate) fn operations(source: &str, target: &str) -> Vec<Operation> {
+    let a: Vec<char> = source.chars().collect();
+    let b: Vec<char> = target.chars().collect();
+    let (n, m) = (a.len(), b.len());
+
+    // `dist[i][j]` is the distance between `a[..i]` and `b[..j]`, and `transposed[i][j]` the
+    // cell the transpose ending at `(i, j)` comes from, if it's the cheapest way there
+    let mut dist = vec![vec![0usize; m + 1]; n + 1];
+    let mut transposed: Vec<Vec<Option<(usize, usize)>>> = vec![vec![None; m + 1]; n + 1];
+
+    for (i, row) in dist.iter_mut().enumerate() {
+        row[0] = i;
+    }
+
+    for (j, cell) in dist[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    // the last row where each character has been seen in the source
+    let mut last_row: HashMap<char, usize> = HashMap::new();
+
+    for i in 1..=n {
+        let mut last_col = 0;
+
+        for j in 1..=m {
+            let i1 = last_row.get(&b[j - 1]).cloned().unwrap_or(0);
+            let j1 = last_col;
+
+            let cost = if a[i - 1] == b[j - 1] {
+                last_col = j;
+                0
+            } else {
+                1
+            };
+
+            let mut best = (dist[i - 1][j - 1] + cost)
+                .min(dist[i - 1][j] + 1)
+                .min(dist[i][j - 1] + 1);
+
+            if i1 > 0 && j1 > 0 {
+                let swap = dist[i1 - 1][j1 - 1] + (i - i1 - 1) + 1 + (j - j1 - 1);
+                if swap < best {
+                    best = swap;
+                    transposed[i][j] = Some((i1, j1));
+                }
+            }
+
+            dist[i][j] = best;
+        }
+
+        last_row.insert(a[i - 1], i);
+    }
+
+    // walk back from the full words, collecting the operations in the reversed order
+    let mut result = Vec::with_capacity(dist[n][m]);
+    let (mut i, mut j) = (n, m);
+
+    while i > 0 || j > 0 {
+        if i > 0 && j > 0 {
+            if let Some((i1, j1)) = transposed[i][j] {
+                for rune in b[j1..j - 1].iter().rev() {
+                    result.push(Operation::Insert { pos: i1, rune: *rune });
+                }
+
+                for pos in (i1..i - 1).rev() {
+                    result.push(Operation::Delete { pos, rune: a[pos] });
+                }
+
+                result.push(Operation::Transpose(i1 - 1, i - 1));
+                i = i1 - 1;
+                j = j1 - 1;
+                continue;
+            }
+
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            if dist[i][j] == dist[i - 1][j - 1] + cost {
+                if cost == 1 {
+                    result.push(Operation::Replace { pos: i - 1, from: a[i - 1], to: b[j - 1] });
+                }
+
+                i -= 1;
+                j -= 1;
+                continue;
+            }
+        }
+
+        if i > 0 && dist[i][j] == dist[i - 1][j] + 1 {
+            result.push(Operation::Delete { pos: i - 1, rune: a[i - 1] });
+            i -= 1;
+        } else {
+            result.push(Operation::Insert { pos: i, rune: b[j - 1] });
+            j -= 1;
+        }
+    }
+
+    result.reverse();
+    result.sort_by_key(Operation::position);
+    result
+}
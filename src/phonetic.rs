@@ -0,0 +1,474 @@
+use crossbeam_channel as channel;
+use hashbrown::{HashMap, HashSet};
+
+use candidate::{Candidate, MatchKind};
+use common;
+use trie::Trie;
+
+static KEY_LENGTH: usize = 4;
+
+/// The phonetic algorithms that map the words sounding alike to the same keys.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Phonetic {
+    /// The American Soundex: the first letter, then the digits of the following consonants.
+    Soundex,
+    /// The Double Metaphone: a primary key, plus an alternate one for the ambiguous spellings.
+    DoubleMetaphone,
+}
+
+impl Phonetic {
+    /// The distinct keys of the `word`; words with a common key sound alike.
+    pub fn keys(&self, word: &str) -> Vec<String> {
+        let mut keys = match self {
+            Phonetic::Soundex => vec![soundex(word)],
+            Phonetic::DoubleMetaphone => {
+                let (primary, alternate) = double_metaphone(word);
+                vec![primary, alternate]
+            },
+        };
+
+        keys.retain(|key| !key.is_empty());
+        keys.dedup();
+        keys
+    }
+}
+
+/// The dictionary words, grouped by their phonetic keys.
+#[derive(Debug)]
+pub(crate) struct PhoneticIndex {
+    algorithm: Phonetic,
+    words: HashMap<String, Vec<String>>,
+}
+
+impl PhoneticIndex {
+    pub(crate) fn build(dict: &Trie, algorithm: Phonetic) -> Self {
+        let mut words: HashMap<String, Vec<String>> = HashMap::new();
+
+        for (word, _) in dict.words() {
+            for key in algorithm.keys(&word) {
+                words.entry(key).or_insert_with(Vec::new).push(word.clone());
+            }
+        }
+
+        PhoneticIndex { algorithm, words }
+    }
+}
+
+/// Find the dictionary words sharing a phonetic key with the `word`, however many edits away.
+pub(crate) fn candidate(
+    word: String,
+    dict: &Trie,
+    index: &PhoneticIndex,
+    tx_async: &channel::Sender<Candidate>
+) {
+    let word = word.trim().to_lowercase();
+    if word.is_empty() {
+        return;
+    }
+
+    let mut found: HashSet<&str> = HashSet::new();
+
+    for key in index.algorithm.keys(&word) {
+        let words = match index.words.get(&key) {
+            Some(words) => words,
+            None => continue,
+        };
+
+        for known in words {
+            if !found.insert(known) {
+                continue;
+            }
+
+            let score = dict.check(known).unwrap_or(0);
+            let edit = common::damerau_distance(&word, known).min(u8::MAX as usize) as u8;
+
+            let mut candidate = Candidate::new(known.to_owned(), score, edit);
+            candidate.kind = MatchKind::Phonetic;
+
+            if tx_async.send(candidate).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn soundex(word: &str) -> String {
+    let code = |rune: char| match rune {
+        'B' | 'F' | 'P' | 'V' => Some('1'),
+        'C' | 'G' | 'J' | 'K' | 'Q' | 'S' | 'X' | 'Z' => Some('2'),
+        'D' | 'T' => Some('3'),
+        'L' => Some('4'),
+        'M' | 'N' => Some('5'),
+        'R' => Some('6'),
+        _ => None,
+    };
+
+    let mut runes = word.chars().filter(|rune| rune.is_ascii_alphabetic()).map(|rune| rune.to_ascii_uppercase());
+    let first = match runes.next() {
+        Some(first) => first,
+        None => return String::new(),
+    };
+
+    let mut key = first.to_string();
+    let mut last = code(first);
+
+    for rune in runes {
+        let digit = code(rune);
+
+        // vowels separate the same digits, while 'H' and 'W' don't
+        if digit.is_some() && digit != last {
+            key.push(digit.unwrap_or('0'));
+        }
+
+        if rune != 'H' && rune != 'W' {
+            last = digit;
+        }
+
+        if key.len() == KEY_LENGTH {
+            return key;
+        }
+    }
+
+    while key.len() < KEY_LENGTH {
+        key.push('0');
+    }
+
+    key
+}
+
+/// A condensed Double Metaphone, covering the rules of the English spellings.
+fn double_metaphone(word: &str) -> (String, String) {
+    let word: Vec<char> = word
+        .chars()
+        .filter(|rune| rune.is_ascii_alphabetic())
+        .map(|rune| rune.to_ascii_uppercase())
+        .collect();
+
+    let mut encoder = Encoder { word: &word, primary: String::new(), alternate: String::new() };
+    encoder.encode();
+
+    let Encoder { mut primary, mut alternate, .. } = encoder;
+    primary.truncate(KEY_LENGTH);
+    alternate.truncate(KEY_LENGTH);
+
+    (primary, alternate)
+}
+
+struct Encoder<'a> {
+    word: &'a [char],
+    primary: String,
+    alternate: String,
+}
+
+impl<'a> Encoder<'a> {
+    fn at(&self, pos: isize) -> char {
+        if pos < 0 {
+            return '\0';
+        }
+
+        self.word.get(pos as usize).cloned().unwrap_or('\0')
+    }
+
+    fn is_vowel(&self, pos: isize) -> bool {
+        "AEIOUY".contains(self.at(pos))
+    }
+
+    fn matches(&self, pos: isize, any: &[&str]) -> bool {
+        if pos < 0 {
+            return false;
+        }
+
+        let pos = pos as usize;
+        any.iter().any(|pattern| {
+            let len = pattern.len();
+            pos + len <= self.word.len() && self.word[pos..pos + len].iter().cloned().eq(pattern.chars())
+        })
+    }
+
+    fn add(&mut self, primary: &str, alternate: &str) {
+        self.primary.push_str(primary);
+        self.alternate.push_str(alternate);
+    }
+
+    fn encode(&mut self) {
+        let len = self.word.len() as isize;
+        let last = len - 1;
+        let mut pos: isize = 0;
+
+        // the silent first letters
+        if self.matches(0, &["GN", "KN", "PN", "WR", "PS"]) {
+            pos = 1;
+        }
+
+        if self.at(0) == 'X' {
+            self.add("S", "S");
+            pos = 1;
+        }
+
+        while pos < len && (self.primary.len() < KEY_LENGTH || self.alternate.len() < KEY_LENGTH) {
+            let rune = self.at(pos);
+            let next = self.at(pos + 1);
+
+            pos += match rune {
+                'A' | 'E' | 'I' | 'O' | 'U' | 'Y' => {
+                    // only the leading vowel counts
+                    if pos == 0 {
+                        self.add("A", "A");
+                    }
+
+                    1
+                },
+                'B' => {
+                    self.add("P", "P");
+                    if next == 'B' { 2 } else { 1 }
+                },
+                'C' => self.encode_c(pos),
+                'D' => {
+                    if self.matches(pos, &["DG"]) {
+                        if "IEY".contains(self.at(pos + 2)) {
+                            self.add("J", "J");
+                            3
+                        } else {
+                            self.add("TK", "TK");
+                            2
+                        }
+                    } else {
+                        self.add("T", "T");
+                        if self.matches(pos, &["DT", "DD"]) { 2 } else { 1 }
+                    }
+                },
+                'F' | 'V' => {
+                    self.add("F", "F");
+                    if next == rune { 2 } else { 1 }
+                },
+                'G' => self.encode_g(pos),
+                // only voiced before a vowel, and not after a consonant
+                'H' if (pos == 0 || self.is_vowel(pos - 1)) && self.is_vowel(pos + 1) => {
+                    self.add("H", "H");
+                    2
+                },
+                'J' => {
+                    if self.matches(pos, &["JOSE"]) {
+                        self.add("H", "H");
+                    } else if pos == 0 {
+                        self.add("J", "A");
+                    } else {
+                        self.add("J", "J");
+                    }
+
+                    if next == 'J' { 2 } else { 1 }
+                },
+                'K' | 'Q' => {
+                    self.add("K", "K");
+                    if next == rune { 2 } else { 1 }
+                },
+                'L' | 'N' | 'R' => {
+                    let code = rune.to_string();
+                    self.add(&code, &code);
+                    if next == rune { 2 } else { 1 }
+                },
+                'M' => {
+                    self.add("M", "M");
+
+                    // "dumb", "thumb"
+                    if next == 'M' || (next == 'B' && pos + 1 == last) { 2 } else { 1 }
+                },
+                'P' => {
+                    if next == 'H' {
+                        self.add("F", "F");
+                        2
+                    } else {
+                        self.add("P", "P");
+                        if next == 'P' || next == 'B' { 2 } else { 1 }
+                    }
+                },
+                'S' => self.encode_s(pos),
+                'T' => self.encode_t(pos),
+                'W' => {
+                    if next == 'R' {
+                        self.add("R", "R");
+                        2
+                    } else {
+                        if pos == 0 && self.is_vowel(pos + 1) {
+                            self.add("A", "F");
+                        } else if pos == 0 && next == 'H' {
+                            self.add("A", "A");
+                        }
+
+                        1
+                    }
+                },
+                'X' => {
+                    // the French "eau", "oux" at the end are silent
+                    let silent = pos == last
+                        && (self.matches(pos - 3, &["IAU", "EAU"]) || self.matches(pos - 2, &["AU", "OU"]));
+
+                    if !silent {
+                        self.add("KS", "KS");
+                    }
+
+                    if next == 'C' || next == 'X' { 2 } else { 1 }
+                },
+                'Z' => {
+                    if next == 'H' {
+                        self.add("J", "J");
+                        2
+                    } else {
+                        self.add("S", "S");
+                        if next == 'Z' { 2 } else { 1 }
+                    }
+                },
+                _ => 1,
+            };
+        }
+    }
+
+    fn encode_c(&mut self, pos: isize) -> isize {
+        if self.matches(pos, &["CH"]) {
+            // the Greek roots, and "school"
+            let greek = pos == 0 && self.matches(1, &["HARAC", "HARIS", "HOR", "HYM", "HIA", "HEM"]);
+            if greek || (pos > 0 && self.at(pos - 1) == 'S') {
+                self.add("K", "K");
+            } else {
+                self.add("X", "K");
+            }
+
+            return 2;
+        }
+
+        if self.matches(pos, &["CIA"]) {
+            self.add("X", "X");
+            return 3;
+        }
+
+        if self.matches(pos, &["CC"]) && "IEH".contains(self.at(pos + 2)) && !self.matches(pos + 2, &["HU"]) {
+            // "accident", "succeed"
+            self.add("KS", "KS");
+            return 3;
+        }
+
+        if self.matches(pos, &["CK", "CG", "CQ", "CC"]) {
+            self.add("K", "K");
+            return 2;
+        }
+
+        if self.matches(pos, &["CI", "CE", "CY"]) {
+            if self.matches(pos, &["CIO", "CIE"]) {
+                self.add("S", "X");
+            } else {
+                self.add("S", "S");
+            }
+
+            return 2;
+        }
+
+        self.add("K", "K");
+        1
+    }
+
+    fn encode_g(&mut self, pos: isize) -> isize {
+        let next = self.at(pos + 1);
+
+        if next == 'H' {
+            if pos > 0 && !self.is_vowel(pos - 1) {
+                self.add("K", "K");
+            } else if pos == 0 {
+                if self.at(pos + 2) == 'I' {
+                    self.add("J", "J");
+                } else {
+                    self.add("K", "K");
+                }
+            } else if "BHD".contains(self.at(pos - 2))
+                || "BH".contains(self.at(pos - 3))
+                || "BH".contains(self.at(pos - 4)) {
+                // "bough", "night", "brought" are silent
+            } else if pos > 2 && self.at(pos - 1) == 'U' && "CGLRT".contains(self.at(pos - 3)) {
+                // "laugh", "cough", "rough"
+                self.add("F", "F");
+            } else if pos > 0 && self.at(pos - 1) != 'I' {
+                self.add("K", "K");
+            }
+
+            return 2;
+        }
+
+        if next == 'N' {
+            // "gnome", "sign", "campaign"
+            self.add("N", "N");
+            return 2;
+        }
+
+        if "EIY".contains(next) {
+            // "germ" or "get"
+            self.add("J", "K");
+            return 2;
+        }
+
+        self.add("K", "K");
+        if next == 'G' { 2 } else { 1 }
+    }
+
+    fn encode_s(&mut self, pos: isize) -> isize {
+        let next = self.at(pos + 1);
+
+        if self.matches(pos - 1, &["ISL", "YSL"]) {
+            // "island", "isle"
+            return 1;
+        }
+
+        if pos == 0 && self.matches(pos, &["SUGAR"]) {
+            self.add("X", "S");
+            return 1;
+        }
+
+        if next == 'H' {
+            self.add("X", "X");
+            return 2;
+        }
+
+        if self.matches(pos, &["SIO", "SIA"]) {
+            self.add("S", "X");
+            return 3;
+        }
+
+        if self.matches(pos, &["SC"]) {
+            let after = self.at(pos + 2);
+
+            if after == 'H' {
+                if self.matches(pos + 3, &["OO", "ER", "EN", "UY", "ED", "EM"]) {
+                    // "school", "schooner"
+                    self.add("SK", "SK");
+                } else {
+                    self.add("X", "SK");
+                }
+            } else if "IEY".contains(after) {
+                self.add("S", "S");
+            } else {
+                self.add("SK", "SK");
+            }
+
+            return 3;
+        }
+
+        self.add("S", "S");
+        if next == 'S' || next == 'Z' { 2 } else { 1 }
+    }
+
+    fn encode_t(&mut self, pos: isize) -> isize {
+        let next = self.at(pos + 1);
+
+        if self.matches(pos, &["TION", "TIA", "TCH"]) {
+            self.add("X", "X");
+            return 3;
+        }
+
+        if next == 'H' || self.matches(pos, &["TTH"]) {
+            self.add("0", "T");
+            return if next == 'H' { 2 } else { 3 };
+        }
+
+        self.add("T", "T");
+        if next == 'T' || next == 'D' { 2 } else { 1 }
+    }
+}
+
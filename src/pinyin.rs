@@ -0,0 +1,253 @@
+use std::io::BufRead;
+
+use crossbeam_channel as channel;
+use hashbrown::HashMap;
+
+use candidate::{Candidate, MatchKind};
+use common;
+use config::DictSource;
+use error::Error;
+use trie::Trie;
+
+/// The most spellings kept for a word of several characters with more than one reading.
+const MAX_SPELLINGS: usize = 8;
+
+/// The pinyin of the `ZhCn` dictionary: how each character reads, which characters sound alike,
+/// and how each word is typed on a pinyin IME.
+#[derive(Debug, Default)]
+pub(crate) struct PinyinIndex {
+    /// The toneless syllables of each character, e.g. "zhong" and "chong" for "重".
+    readings: HashMap<char, Vec<String>>,
+    /// The characters sounding like each character, itself included.
+    alike: HashMap<char, Vec<char>>,
+    /// The spellings of the words, e.g. "zhongguo" for "中国", walked by the edits of the input.
+    spellings: Trie,
+    /// The words of each spelling, with the lengths of the syllables they're spelled with, e.g.
+    /// `[2, 2]` for "西安" and `[4]` for "线", both spelled "xian".
+    words: HashMap<String, Vec<(String, Vec<u8>)>>,
+}
+
+impl PinyinIndex {
+    /// Load the readings from the `source`, one syllable per line followed by its characters,
+    /// e.g. `zai 在再灾载`, and spell the words of the `dict` with them. The words with any
+    /// character missing from the readings can't be spelled, and are left out.
+    pub(crate) fn build(source: DictSource, dict: &Trie) -> Result<Self, Error> {
+        let path = source.describe("(embedded pinyin)");
+
+        let reader = common::open_dict(source)?;
+        let mut index = PinyinIndex::default();
+        let mut sounds: HashMap<String, Vec<char>> = HashMap::new();
+
+        for (idx, line) in reader.lines().enumerate() {
+            let line = line.map_err(|err| Error::io(&path, err))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let (syllable, runes) = match line.trim().split_once(char::is_whitespace) {
+                Some((syllable, runes)) if syllable.chars().all(|rune| rune.is_ascii_lowercase()) => {
+                    (syllable, runes.trim())
+                },
+                _ => return Err(Error::MalformedLine { line: idx + 1, content: line.to_owned() }),
+            };
+
+            for rune in runes.chars() {
+                index.readings.entry(rune).or_default().push(syllable.to_owned());
+                sounds.entry(fuzzy(syllable)).or_default().push(rune);
+            }
+        }
+
+        for (rune, readings) in index.readings.iter() {
+            let mut alike: Vec<char> = Vec::new();
+
+            for sound in readings.iter().filter_map(|reading| sounds.get(&fuzzy(reading))) {
+                for other in sound {
+                    if !alike.contains(other) {
+                        alike.push(*other);
+                    }
+                }
+            }
+
+            index.alike.insert(*rune, alike);
+        }
+
+        for (word, _) in dict.words() {
+            for (spelling, syllables) in index.spell(&word) {
+                let words = index.words.entry(spelling.to_owned()).or_default();
+                if words.is_empty() {
+                    index.spellings.insert(spelling, 0);
+                }
+
+                words.push((word.to_owned(), syllables));
+            }
+        }
+
+        Ok(index)
+    }
+
+    /// The ways to type the `word` in pinyin, one per combination of the readings of its
+    /// characters, up to `MAX_SPELLINGS` of them, each with the lengths of its syllables. None
+    /// if a character has no known reading.
+    fn spell(&self, word: &str) -> Vec<(String, Vec<u8>)> {
+        let mut readings = Vec::new();
+
+        // find the readings of all the characters first, most words have one without any
+        for rune in word.chars() {
+            match self.readings.get(&rune) {
+                Some(found) => readings.push(found),
+                None => return Vec::new(),
+            }
+        }
+
+        let mut spellings: Vec<Vec<&str>> = vec![Vec::new()];
+
+        for found in readings {
+            spellings = spellings
+                .iter()
+                .flat_map(|syllables| found.iter().map(move |reading| {
+                    let mut syllables = syllables.clone();
+                    syllables.push(reading.as_str());
+                    syllables
+                }))
+                .take(MAX_SPELLINGS)
+                .collect();
+        }
+
+        spellings
+            .into_iter()
+            .map(|syllables| (syllables.concat(), syllables.iter().map(|s| s.len() as u8).collect()))
+            .collect()
+    }
+
+    fn alike(&self, rune: char) -> &[char] {
+        self.alike.get(&rune).map_or(&[], |alike| alike.as_slice())
+    }
+}
+
+/// Send the words the input stands for: the words it spells in pinyin if it's typed in latin
+/// letters, within `max_edit` of their spellings, or else the words reached by replacing up to
+/// `max_edit` of its characters with the ones sounding alike.
+pub(crate) fn candidate(
+    word: String,
+    max_edit: u8,
+    dict: &Trie,
+    index: &PinyinIndex,
+    tx_async: &channel::Sender<Candidate>
+) {
+    let word = word.trim().to_lowercase();
+    if word.is_empty() {
+        return;
+    }
+
+    // the syllables may be typed apart, e.g. "xi'an" or "zhong guo"
+    if word.chars().all(|rune| rune.is_ascii_lowercase() || rune == '\'' || rune == ' ') {
+        let spelling: String = word.chars().filter(char::is_ascii_lowercase).collect();
+        let typed = separated(&word);
+
+        index.spellings.walk(&spelling, max_edit, |spelling, _, distance| {
+            let words = match index.words.get(spelling) {
+                Some(words) => words,
+                None => return true,
+            };
+
+            words.iter().all(|(known, syllables)| {
+                // a separator typed within a syllable is one more edit, e.g. "xi'an" for "线"
+                let distance = match typed.as_ref() {
+                    Some(typed) => match separated_distance(typed, spelling, syllables) {
+                        distance if distance <= max_edit as usize => distance as u8,
+                        _ => return true,
+                    },
+                    None => distance,
+                };
+
+                let score = dict.check(known).unwrap_or(0);
+                let mut candidate = Candidate::new(known.to_owned(), score, distance);
+                candidate.kind = MatchKind::Pinyin;
+
+                tx_async.send(candidate).is_ok()
+            })
+        });
+
+        return;
+    }
+
+    dict.substitute(&word, max_edit, |rune| index.alike(rune), |known, score, replaced| {
+        let mut candidate = Candidate::new(known.to_owned(), score, replaced);
+        if replaced > 0 {
+            candidate.kind = MatchKind::Phonetic;
+        }
+
+        tx_async.send(candidate).is_ok()
+    });
+}
+
+/// Whether the `word` has any Chinese character, from the CJK Unified Ideographs or their
+/// Extension A.
+pub(crate) fn has_han(word: &str) -> bool {
+    word.chars().any(|rune| matches!(rune, '\u{3400}'..='\u{4dbf}' | '\u{4e00}'..='\u{9fff}'))
+}
+
+/// The `word` typed in pinyin with its syllables apart, each run of separators made a single
+/// `'`, e.g. "xi'an" for "xi' an", or `None` if the syllables aren't typed apart.
+fn separated(word: &str) -> Option<String> {
+    let syllables: Vec<&str> = word
+        .split(['\'', ' '])
+        .filter(|syllable| !syllable.is_empty())
+        .collect();
+
+    if syllables.len() > 1 {
+        Some(syllables.join("'"))
+    } else {
+        None
+    }
+}
+
+/// The distance from the `typed` syllables to the `spelling` made of the `syllables` lengths,
+/// with the separators put between any of its syllables: only those typed where the spelling
+/// has none, or missing where they're typed, count as edits.
+fn separated_distance(typed: &str, spelling: &str, syllables: &[u8]) -> usize {
+    let bounds = syllables.len().saturating_sub(1);
+    let mut best = usize::MAX;
+
+    for mask in 0u32..(1 << bounds.min(16)) {
+        let mut rendered = String::with_capacity(spelling.len() + bounds);
+        let mut at = 0;
+
+        for (idx, len) in syllables.iter().enumerate() {
+            if idx > 0 && mask & (1 << (idx - 1)) != 0 {
+                rendered.push('\'');
+            }
+
+            rendered.push_str(&spelling[at..at + *len as usize]);
+            at += *len as usize;
+        }
+
+        best = best.min(common::damerau_distance(typed, &rendered));
+    }
+
+    best
+}
+
+/// The `syllable` with the sounds often told apart poorly merged, as the fuzzy pinyin options
+/// of the IMEs do: the retroflex initials with the flat ones, "l" with "n", and the back nasal
+/// finals with the front ones.
+fn fuzzy(syllable: &str) -> String {
+    let mut sound = syllable.to_owned();
+
+    for (from, to) in [("zh", "z"), ("ch", "c"), ("sh", "s"), ("l", "n")].iter() {
+        if sound.starts_with(from) {
+            sound.replace_range(..from.len(), to);
+            break;
+        }
+    }
+
+    for (from, to) in [("ang", "an"), ("eng", "en"), ("ing", "in")].iter() {
+        if sound.ends_with(from) {
+            let at = sound.len() - from.len();
+            sound.replace_range(at.., to);
+            break;
+        }
+    }
+
+    sound
+}
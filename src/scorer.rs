@@ -0,0 +1,113 @@
+use candidate::Candidate;
+use keyboard::Keyboard;
+
+/// Ranks the candidates of a query: the higher the rank, the earlier the candidate is listed.
+/// Set a custom one on the `Config` to re-rank the candidates, e.g. by a domain vocabulary.
+pub trait Scorer: Send + Sync {
+    /// The rank of the `candidate` found for the `input` word, where `candidate.edit` carries
+    /// its edit distance from the input, `candidate.operations` the edits themselves, and
+    /// `candidate.score` its frequency in the dictionary.
+    fn rank(&self, input: &str, candidate: &Candidate) -> f64;
+
+    /// The chance that the `candidate` is the word meant by the `input`, if the scorer is a
+    /// probabilistic one. It doesn't need to add up to 1 over the candidates, since the service
+    /// normalizes it before setting it on `Candidate::probability`.
+    fn probability(&self, _input: &str, _candidate: &Candidate) -> Option<f64> {
+        None
+    }
+
+    /// Whether every candidate ranks above all the candidates with more edits, which lets
+    /// `AutoCorrect::candidates_top_k` stop at the first edit distance with enough candidates.
+    fn ranks_edits_first(&self) -> bool {
+        false
+    }
+}
+
+impl<F> Scorer for F where F: Fn(&str, &Candidate) -> f64 + Send + Sync {
+    fn rank(&self, input: &str, candidate: &Candidate) -> f64 {
+        self(input, candidate)
+    }
+}
+
+/// The default ranking: the fewer edits the better, then the more frequent the better.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EditsThenFrequency;
+
+impl Scorer for EditsThenFrequency {
+    fn rank(&self, _input: &str, candidate: &Candidate) -> f64 {
+        // a frequency always fits in 32 bits, so it never outweighs an edit
+        -f64::from(candidate.edit) * f64::from(u32::MAX) * 2.0 + f64::from(candidate.score)
+    }
+
+    fn ranks_edits_first(&self) -> bool {
+        true
+    }
+}
+
+// the costs of the typos, relative to each other
+static INDEL_COST: f64 = 1.0;
+static TRANSPOSE_COST: f64 = 1.0;
+static NEAR_KEY_COST: f64 = 1.25;
+static FAR_KEY_COST: f64 = 2.0;
+
+/// Rank by how likely the input is a typo of the candidate on the given keyboard, then by the
+/// frequency. Dropping, doubling or swapping keys are the cheapest typos, followed by hitting a
+/// key next to the intended one, while replacing a key with a distant one costs the most.
+#[derive(Debug, Clone, Default)]
+pub struct KeyboardScorer {
+    keyboard: Keyboard,
+}
+
+impl KeyboardScorer {
+    pub fn new(keyboard: Keyboard) -> Self {
+        KeyboardScorer { keyboard }
+    }
+
+    /// The cheapest sequence of typos turning the `word` into the `input`, where each
+    /// character is edited at most once.
+    pub fn typo_cost(&self, input: &str, word: &str) -> f64 {
+        let a: Vec<char> = input.chars().collect();
+        let b: Vec<char> = word.chars().collect();
+        let mut table = vec![vec![0f64; b.len() + 1]; a.len() + 1];
+
+        for (i, row) in table.iter_mut().enumerate() {
+            row[0] = i as f64 * INDEL_COST;
+        }
+
+        for (j, cell) in table[0].iter_mut().enumerate() {
+            *cell = j as f64 * INDEL_COST;
+        }
+
+        for i in 1..=a.len() {
+            for j in 1..=b.len() {
+                let replace =
+                    if a[i - 1] == b[j - 1] {
+                        0.0
+                    } else if self.keyboard.is_near(a[i - 1], b[j - 1]) {
+                        NEAR_KEY_COST
+                    } else {
+                        FAR_KEY_COST
+                    };
+
+                let mut cost = (table[i - 1][j - 1] + replace)
+                    .min(table[i - 1][j] + INDEL_COST)
+                    .min(table[i][j - 1] + INDEL_COST);
+
+                if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                    cost = cost.min(table[i - 2][j - 2] + TRANSPOSE_COST);
+                }
+
+                table[i][j] = cost;
+            }
+        }
+
+        table[a.len()][b.len()]
+    }
+}
+
+impl Scorer for KeyboardScorer {
+    fn rank(&self, input: &str, candidate: &Candidate) -> f64 {
+        -self.typo_cost(input, &candidate.word) * f64::from(u32::MAX) * 2.0
+            + f64::from(candidate.score)
+    }
+}
@@ -0,0 +1,97 @@
+use hashbrown::HashMap;
+
+use candidate::Candidate;
+use language::{LanguageModel, SENTENCE_END, SENTENCE_START};
+
+// the most candidates of a token the decoder weighs
+static MAX_STATES: usize = 8;
+
+// the log10 chance of each edit between the typed token and the word meant
+static EDIT_LOG_PROB: f64 = -2.0;
+
+/// The words a token may stand for, with the log10 chance of typing the token for each: the
+/// best ranked candidates, plus the token itself if it's a known word. A token without any
+/// candidate stands for itself.
+pub(crate) fn states(token: &str, candidates: Vec<Candidate>) -> Vec<(String, f64)> {
+    let mut states: Vec<(String, f64)> = Vec::with_capacity(MAX_STATES + 1);
+
+    for (idx, candidate) in candidates.into_iter().enumerate() {
+        if idx < MAX_STATES || candidate.edit == 0 {
+            states.push((candidate.word, f64::from(candidate.edit) * EDIT_LOG_PROB));
+        }
+    }
+
+    if states.is_empty() {
+        states.push((token.to_lowercase(), 0.0));
+    }
+
+    states
+}
+
+/// Viterbi decoding: the index of the state picked for each token, such that the sequence of
+/// words is the most likely under the `model`, weighed by the chance of the typos. The states
+/// are the choices for the last `order - 1` tokens, the history the model looks at.
+pub(crate) fn decode(lattice: &[Vec<(String, f64)>], model: &LanguageModel) -> Vec<usize> {
+    let history = model.order().saturating_sub(1);
+
+    // the best path into each state, in the order the states were first reached
+    let mut paths: Vec<(f64, Vec<usize>)> = vec![(0.0, Vec::new())];
+
+    for states in lattice.iter() {
+        let mut next: Vec<(f64, Vec<usize>)> = Vec::new();
+        let mut ids: HashMap<Vec<usize>, usize> = HashMap::new();
+
+        for (score, path) in paths.iter() {
+            let context = context(lattice, path, history);
+
+            for (idx, (word, emission)) in states.iter().enumerate() {
+                let score = score + emission + model.log_prob(&context, word);
+                let mut path = path.clone();
+                path.push(idx);
+
+                let key = path[path.len().saturating_sub(history)..].to_vec();
+                match ids.get(&key) {
+                    Some(id) if next[*id].0 >= score => {},
+                    Some(id) => next[*id] = (score, path),
+                    None => {
+                        ids.insert(key, next.len());
+                        next.push((score, path));
+                    },
+                }
+            }
+        }
+
+        paths = next;
+    }
+
+    let mut best: Option<(f64, Vec<usize>)> = None;
+    for (score, path) in paths {
+        let score = score + model.log_prob(&context(lattice, &path, history), SENTENCE_END);
+
+        let better = match best.as_ref() {
+            Some((best, _)) => score > *best,
+            None => true,
+        };
+
+        if better {
+            best = Some((score, path));
+        }
+    }
+
+    best.map(|(_, path)| path).unwrap_or_default()
+}
+
+/// The last `history` words on the `path`, after the sentence start marker.
+fn context<'a>(lattice: &'a [Vec<(String, f64)>], path: &[usize], history: usize) -> Vec<&'a str> {
+    let mut words: Vec<&str> = Vec::with_capacity(history + 1);
+    if path.len() < history {
+        words.push(SENTENCE_START);
+    }
+
+    let from = path.len().saturating_sub(history);
+    for (pos, idx) in path.iter().enumerate().skip(from) {
+        words.push(&lattice[pos][*idx].0);
+    }
+
+    words
+}
@@ -0,0 +1,121 @@
+use crossbeam_channel as channel;
+
+use candidate::{Candidate, MatchKind};
+use trie::Trie;
+
+const MIN_COMPOUND_PART: usize = 3;
+const MAX_COMPOUND_PARTS: usize = 4;
+
+/// Send the ways to split the `word` into known words, as many as `max_edit` spaces allow, each
+/// scored by the combined frequency of its parts: how often they'd be seen together if they
+/// were independent of each other.
+pub(crate) fn candidate(
+    word: String,
+    max_edit: u8,
+    dict: &Trie,
+    tx_async: &channel::Sender<Candidate>
+) {
+    let word = word.trim().to_lowercase();
+    let runes: Vec<char> = word.chars().collect();
+    if runes.len() < 2 {
+        return;
+    }
+
+    let mut parts = Vec::new();
+    split(&runes, max_edit as usize + 1, dict, &mut parts, &mut |parts| {
+        let words: Vec<String> = parts.iter().map(|(word, _)| word.to_owned()).collect();
+        let scores: Vec<u32> = parts.iter().map(|(_, score)| *score).collect();
+
+        let score = combined(&scores, dict.total());
+        let mut candidate = Candidate::new(words.join(" "), score, (parts.len() - 1) as u8);
+        candidate.kind = MatchKind::Split;
+
+        tx_async.send(candidate).is_ok()
+    });
+}
+
+/// The known word the two adjacent words may have been split from, if it's more frequent than
+/// the two words seen together.
+pub(crate) fn join(first: &str, second: &str, dict: &Trie) -> Option<Candidate> {
+    let joined = format!("{}{}", first.to_lowercase(), second.to_lowercase());
+    let score = dict.check(&joined)?;
+
+    let apart = [first, second]
+        .iter()
+        .map(|word| dict.check(&word.to_lowercase()))
+        .collect::<Option<Vec<u32>>>()
+        .map_or(0, |scores| combined(&scores, dict.total()));
+
+    if score <= apart {
+        return None;
+    }
+
+    let mut candidate = Candidate::new(joined, score, 1);
+    candidate.kind = MatchKind::Join;
+
+    Some(candidate)
+}
+
+/// Whether the `word` is made of known words written together, e.g. "doorhandle" of "door" and
+/// "handle". The parts are at least `MIN_COMPOUND_PART` runes long, or the short words would make
+/// up about anything.
+pub(crate) fn compound(word: &str, dict: &Trie) -> bool {
+    let runes: Vec<char> = word.chars().collect();
+    let mut parts = Vec::new();
+
+    !split(&runes, MAX_COMPOUND_PARTS, dict, &mut parts, &mut |parts| {
+        parts.iter().any(|(part, _)| part.chars().count() < MIN_COMPOUND_PART)
+    })
+}
+
+/// Hand each split of the `runes` into 2 to `max_parts` known words to `found`, until it
+/// returns `false`.
+fn split<F>(
+    runes: &[char],
+    max_parts: usize,
+    dict: &Trie,
+    parts: &mut Vec<(String, u32)>,
+    found: &mut F
+) -> bool
+    where F: FnMut(&[(String, u32)]) -> bool
+{
+    if runes.is_empty() {
+        return parts.len() < 2 || found(parts);
+    }
+
+    // the last part takes all the runes left
+    let last = parts.len() + 1 == max_parts;
+
+    for len in 1..=runes.len() {
+        if last && len < runes.len() {
+            continue;
+        }
+
+        // the whole word isn't a split of itself
+        if parts.is_empty() && len == runes.len() {
+            break;
+        }
+
+        let word: String = runes[..len].iter().collect();
+        if let Some(score) = dict.check(&word) {
+            parts.push((word, score));
+            let more = split(&runes[len..], max_parts, dict, parts, found);
+            parts.pop();
+
+            if !more {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+/// The frequency of the words seen together, out of the `total` count of the dictionary, if
+/// each of them is as likely as on its own.
+fn combined(scores: &[u32], total: u64) -> u32 {
+    let total = total.max(1) as f64;
+    let chance: f64 = scores.iter().map(|score| f64::from(*score) / total).product();
+
+    (chance * total).round().min(f64::from(u32::MAX)) as u32
+}
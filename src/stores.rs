@@ -1,110 +1,44 @@
-use hashbrown::HashSet;
-use std::sync::atomic::{AtomicBool, Ordering};
-use candidate::Candidate;
-use std::sync::Once;
-
-static mut STORE: Option<HashSet<String>> = None;
-static mut RESULTS: Option<HashSet<Candidate>> = None;
-
-static LOCKED: [AtomicBool; 2] = [AtomicBool::new(false), AtomicBool::new(false)];
-static READY: AtomicBool = AtomicBool::new(false);
-static ONCE: Once = Once::new();
-
-pub(crate) fn get_ready() {
-    READY.store(true, Ordering::SeqCst);
-    ONCE.call_once(|| unsafe {
-        STORE.replace(HashSet::with_capacity(256));
-        RESULTS.replace(HashSet::with_capacity(64));
-    });
-}
-
-pub(crate) fn reset() {
-    READY.store(false, Ordering::SeqCst);
-
-    unsafe {
-        if let Some(store) = STORE.as_mut() {
-            store.clear();
-        }
-
-        if let Some(res) = RESULTS.as_mut() {
-            res.clear();
-        }
-    }
-}
-
-pub(crate) fn contains(word: &str) -> bool {
-    if !READY.load(Ordering::SeqCst) {
-        return true;
-    }
-
-    let mut contains = true;
-    unsafe {
-        if let Some(store) = STORE.as_mut() {
-            if !store.contains(word) {
-                lock(0);
-                contains = !store.insert(word.to_owned());
-                unlock(0);
-            }
-        }
-    }
-
-    contains
+use hashbrown::HashMap;
+use std::sync::Mutex;
+
+/// The words the fan-out of a single query has already visited, with the fewest edits they
+/// have been reached by. Every query owns its store, so concurrent queries never see each
+/// other's progress.
+pub(crate) struct Store {
+    visited: Mutex<HashMap<String, u8>>,
 }
 
-pub(crate) fn publish(candidate: Candidate) -> bool {
-    if !READY.load(Ordering::SeqCst) {
-        return true;
-    }
-
-    unsafe {
-        if let Some(res) = RESULTS.as_mut() {
-            if res.contains(&candidate) {
-                return true;
-            }
-
-            lock(1);
-            res.insert(candidate);
-            unlock(1);
-
-            return false;
+impl Store {
+    pub(crate) fn new() -> Self {
+        Store {
+            visited: Mutex::new(HashMap::with_capacity(256)),
         }
     }
 
-    true
-}
-
-pub(crate) fn collect() -> Vec<Candidate> {
-    lock(1);
-
-    let res =
-        if let Some(res) = unsafe { RESULTS.as_mut() } {
-            let mut vec = Vec::with_capacity(res.len());
-            for candidate in res.drain() {
-                vec.push(candidate);
-            }
-
-            vec
-        } else {
-            vec![]
-        };
-
-    unlock(1);
-    return res;
-}
-
-fn lock(id: usize) {
-    if id < LOCKED.len() {
-        let lock = &LOCKED[id];
-        loop {
-            if lock.compare_and_swap(false, true, Ordering::SeqCst) {
-                break;
-            }
+    /// Check if the `word` has already been visited within `edit` edits, otherwise mark it as
+    /// visited, such that the caller shall carry on searching from it.
+    pub(crate) fn contains(&self, word: &str, edit: u8) -> bool {
+        match self.visited.lock() {
+            Ok(mut visited) => {
+                if let Some(prev) = visited.get_mut(word) {
+                    if *prev <= edit {
+                        return true;
+                    }
+
+                    *prev = edit;
+                    return false;
+                }
+
+                visited.insert(word.to_owned(), edit);
+                false
+            },
+            Err(_) => true,
         }
     }
 }
 
-fn unlock(id: usize) {
-    if id < LOCKED.len() {
-        LOCKED[id].store(false, Ordering::SeqCst);
+impl Default for Store {
+    fn default() -> Self {
+        Store::new()
     }
 }
@@ -1,9 +1,56 @@
+/// The bit of the rune in a 32 bits set: the runes a-z take a bit of their own, and the rest of
+/// Unicode shares the last 6 bits, so the set stays a quick reject for any script.
+#[inline(always)]
+pub(crate) fn get_char_code(rune: char) -> u8 {
+    match rune {
+        'a'..='z' => (rune as u32 - 'a' as u32) as u8,
+        _ => 26 + (rune as u32 % 6) as u8,
+    }
+}
+
 pub(crate) mod en_us {
-    pub(crate) static ALPHABET_EN: &'static str = "abcdefghijklmnopqrstuvwxyz";
-    pub(crate) const ALPHABET: [&str;26] = ["a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n", "o", "p", "q", "r", "s", "t", "u", "v", "w", "x", "y", "z"];
+    /// The runes the edits are made of: the letters, the apostrophe and hyphen of words such as
+    /// "don't" and "x-ray", and the accented letters of the loanwords, e.g. "café" or "naïve".
+    pub(crate) static ALPHABET_EN: &str = "abcdefghijklmnopqrstuvwxyz'-àâçéèêëîïñôöüû";
+    #[cfg(feature = "en-us")]
+    pub(crate) static DICT: &str = include_str!("../resources/en-us/freq_50k.txt");
+    #[cfg(feature = "en-us")]
+    pub(crate) static DICT_PREPROC: &str = include_str!("../resources/en-us/freq_50k_preproc.txt");
+}
 
-    #[inline(always)]
-    pub(crate) fn get_char_code(rune: char) -> u8 {
-        (rune as u32 - 'a' as u32) as u8
-    }
-}
\ No newline at end of file
+pub(crate) mod en_gb {
+    pub(crate) static ALPHABET_EN: &str = "abcdefghijklmnopqrstuvwxyz'-àâçéèêëîïñôöüû";
+    #[cfg(feature = "en-gb")]
+    pub(crate) static DICT: &str = include_str!("../resources/en-gb/freq_50k.txt");
+    #[cfg(feature = "en-gb")]
+    pub(crate) static DICT_PREPROC: &str = include_str!("../resources/en-gb/freq_50k_preproc.txt");
+}
+
+pub(crate) mod de {
+    /// The umlauts and the sharp s, e.g. "Größe", and the hyphen of the compounds written apart
+    /// with one, e.g. "E-Mail".
+    pub(crate) static ALPHABET_DE: &str = "abcdefghijklmnopqrstuvwxyzäöüß-";
+}
+
+pub(crate) mod fr {
+    /// The accents, the cedilla and the ligatures, e.g. "cœur", and the apostrophe and hyphen of
+    /// "aujourd'hui" and "peut-être".
+    pub(crate) static ALPHABET_FR: &str = "abcdefghijklmnopqrstuvwxyz'-àâæçéèêëîïôœùûüÿ";
+}
+
+pub(crate) mod es {
+    /// The acute accents, the ñ and the diaeresis of "pingüino".
+    pub(crate) static ALPHABET_ES: &str = "abcdefghijklmnopqrstuvwxyzáéíñóúü-";
+}
+
+pub(crate) mod it {
+    /// The grave and acute accents, and the apostrophe of the elisions, e.g. "dell'anno".
+    pub(crate) static ALPHABET_IT: &str = "abcdefghijklmnopqrstuvwxyz'-àèéìíîòóùú";
+}
+
+pub(crate) mod zh_cn {
+    #[cfg(feature = "zh-cn")]
+    pub(crate) static DICT: &str = include_str!("../resources/zh-cn/freq.txt");
+    #[cfg(feature = "zh-cn")]
+    pub(crate) static PINYIN: &str = include_str!("../resources/zh-cn/pinyin.txt");
+}
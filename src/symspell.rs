@@ -0,0 +1,127 @@
+use crossbeam_channel as channel;
+use hashbrown::{HashMap, HashSet};
+
+use candidate::Candidate;
+use common;
+use trie::Trie;
+
+// Only the first runes of the words are indexed, as SymSpell does: two words within `n` edits
+// from each other still share a variation of their prefixes within `n` deletes, so the longer
+// words only cost as many deletes as a word of this length, and are told apart by the
+// verification instead.
+const PREFIX_LEN: usize = 7;
+
+// The 64 bits FNV-1a parameters. The deletes are hashed the same way when the index is built
+// and when it's queried, so the hash must not depend on a seed, as the hashers of the maps may.
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// The index for the `SymmetricDelete` mode: every string made by deleting up to `depth`
+/// characters from the prefix of a dictionary word, mapped to the words it's made from. Two
+/// words within `n` edits from each other always share a delete-only variation within `n`
+/// deletes, so a query only needs to generate the deletes of the input, and verify the words
+/// they point to.
+///
+/// The deletes are kept by their hashes rather than spelled out: two deletes sharing a hash
+/// only point a query to more words to verify. With the bundled en-us dictionary, the index
+/// holds 455k keys for 1.1M word ids at depth 2, about 40MB on top of the dictionary, and 528k
+/// keys for 2.2M ids at depth 3, about 60MB.
+#[derive(Debug, Default)]
+pub(crate) struct DeleteIndex {
+    depth: u8,
+    words: Vec<(String, u32)>,
+    // the length of each word in runes, to reject the words too long or too short for the input
+    // before verifying them
+    lens: Vec<usize>,
+    deletes: HashMap<u64, Vec<u32>>,
+}
+
+impl DeleteIndex {
+    pub(crate) fn build(dict: &Trie, depth: u8) -> Self {
+        let words = dict.words();
+        let lens = words.iter().map(|(word, _)| word.chars().count()).collect();
+        let mut deletes: HashMap<u64, Vec<u32>> = HashMap::with_capacity(words.len() * 8);
+
+        for (id, (word, _)) in words.iter().enumerate() {
+            for key in variations(word, depth) {
+                deletes.entry(key).or_insert_with(Vec::new).push(id as u32);
+            }
+        }
+
+        DeleteIndex { depth, words, lens, deletes }
+    }
+
+    /// The max edits the index is built for, a query with more edits needs a deeper index.
+    pub(crate) fn depth(&self) -> u8 {
+        self.depth
+    }
+}
+
+pub(crate) fn candidate(
+    word: String,
+    max_edit: u8,
+    index: &DeleteIndex,
+    tx_async: &channel::Sender<Candidate>
+) {
+    let word = word.trim().to_lowercase();
+    if word.is_empty() {
+        return;
+    }
+
+    let max_edit = max_edit.min(index.depth);
+    let len = word.chars().count();
+    let mut checked: HashSet<u32> = HashSet::with_capacity(64);
+
+    for key in variations(&word, max_edit) {
+        let ids = match index.deletes.get(&key) {
+            Some(ids) => ids,
+            None => continue,
+        };
+
+        for id in ids {
+            // quick reject on the length difference before the full verification
+            let found_len = index.lens[*id as usize];
+            if found_len.max(len) - found_len.min(len) > max_edit as usize || !checked.insert(*id) {
+                continue;
+            }
+
+            let (found, score) = &index.words[*id as usize];
+            let distance = common::damerau_distance(&word, found);
+            if distance <= max_edit as usize
+                && tx_async.send(Candidate::new(found.to_owned(), *score, distance as u8)).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// The hashes of all the strings made by deleting up to `depth` characters from the prefix of
+/// the `word`, including the prefix itself. Each string is picked by the mask of the runes it
+/// keeps, so none is built.
+fn variations(word: &str, depth: u8) -> Vec<u64> {
+    let runes: Vec<char> = word.chars().take(PREFIX_LEN).collect();
+    let len = runes.len();
+    let mut result = Vec::new();
+
+    for mask in 0u32..(1 << len) {
+        if len - (mask.count_ones() as usize) > depth as usize {
+            continue;
+        }
+
+        let mut hash = FNV_OFFSET;
+        for (pos, rune) in runes.iter().enumerate() {
+            if mask & (1 << pos) != 0 {
+                for byte in (*rune as u32).to_le_bytes().iter() {
+                    hash = (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME);
+                }
+            }
+        }
+
+        result.push(hash);
+    }
+
+    // the deletes of the repeated runes, e.g. of either "t" of "tets", spell the same string
+    result.sort_unstable();
+    result.dedup();
+    result
+}
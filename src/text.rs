@@ -0,0 +1,69 @@
+use std::ops::Range;
+
+use candidate::Candidate;
+
+/// A misspelled word in a text.
+#[derive(Debug, Clone)]
+pub struct Issue {
+    /// The byte range of the word in the text.
+    pub span: Range<usize>,
+    /// The word as it's written in the text.
+    pub token: String,
+    /// The candidates for the word, best ranked first.
+    pub suggestions: Vec<Candidate>,
+}
+
+impl Issue {
+    /// The fix with the best ranked suggestion, if there's any.
+    pub fn fix(&self) -> Option<Fix> {
+        self.suggestions
+            .first()
+            .map(|candidate| Fix::new(self.span.clone(), &candidate.word))
+    }
+}
+
+/// A replacement of the text within a byte range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fix {
+    pub span: Range<usize>,
+    pub replacement: String,
+}
+
+impl Fix {
+    pub fn new(span: Range<usize>, replacement: &str) -> Self {
+        Fix { span, replacement: replacement.to_owned() }
+    }
+}
+
+/// Rewrite the `text` with the `fixes`, leaving everything outside their spans untouched. The
+/// fixes may come in any order; a fix overlapping an earlier one, or whose span isn't a valid
+/// range of the text, is skipped.
+pub fn apply(text: &str, fixes: &[Fix]) -> String {
+    let mut fixes: Vec<&Fix> = fixes
+        .iter()
+        .filter(|fix| {
+            fix.span.start <= fix.span.end
+                && fix.span.end <= text.len()
+                && text.is_char_boundary(fix.span.start)
+                && text.is_char_boundary(fix.span.end)
+        })
+        .collect();
+
+    fixes.sort_by_key(|fix| (fix.span.start, fix.span.end));
+
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+
+    for fix in fixes {
+        if fix.span.start < last {
+            continue;
+        }
+
+        result.push_str(&text[last..fix.span.start]);
+        result.push_str(&fix.replacement);
+        last = fix.span.end;
+    }
+
+    result.push_str(&text[last..]);
+    result
+}
@@ -1,12 +1,163 @@
-use std::char;
 use channel::Receiver;
+use hashbrown::HashMap;
 
+use crate::automaton::Automaton;
 use crate::common;
-use crate::support::en_us;
-use support::en_us::get_char_code;
+use crate::error::Error;
+use crate::support;
+
+/// The dictionary owned by a single `AutoCorrect` service: the trie of known words, in lower
+/// case, the spellings of the words listed with capitals, and the bitmap recording which
+/// characters have been seen at each position of any word.
+#[derive(Debug, Default)]
+pub(crate) struct Trie {
+    root: Node,
+    canonical: HashMap<String, String>,
+    total: u64,
+    bitmap: [u32; 32],
+}
+
+impl Trie {
+    pub(crate) fn new() -> Self {
+        Trie::default()
+    }
+
+    pub(crate) fn build(rx: Receiver<Result<(usize, String), Error>>) -> Result<Self, Error> {
+        let mut trie = Trie::new();
+
+        for received in rx {
+            let (line, received) = received?;
+            if received.trim().is_empty() {
+                continue;
+            }
+
+            let (entry, score) = common::split_entry(&received);
+            let score = match score.map(|raw| raw.trim().parse::<u32>()) {
+                Some(Ok(score)) if !entry.is_empty() => score,
+                _ => return Err(Error::MalformedLine { line, content: received.to_owned() }),
+            };
+
+            // as for the scores, the last entry of a word sets its spelling
+            let word = entry.to_lowercase();
+            if word != entry {
+                trie.canonical.insert(word.to_owned(), entry.to_owned());
+            } else {
+                trie.canonical.remove(&word);
+            }
+
+            trie.insert(word, score);
+        }
+
+        Ok(trie)
+    }
+
+    /// Add the lowercase `word`, or update its score, which then replaces the former one in the
+    /// total.
+    pub(crate) fn insert(&mut self, word: String, score: u32) {
+        let vec: Vec<char> = word.chars().collect();
+
+        for (pos, rune) in vec.iter().enumerate() {
+            self.update_bitmap(*rune, pos);
+        }
+
+        if let Some(replaced) = self.root.insert((word, score), vec.as_slice(), 0) {
+            self.total -= u64::from(replaced);
+        }
+
+        self.total += u64::from(score);
+    }
+
+    /// Whether any word has the character of the `char_code` at the `pos`, which the fan-out
+    /// uses to skip the edits that can't make a known word.
+    pub(crate) fn check_bitmap(&self, char_code: u8, pos: usize) -> bool {
+        let shift = if char_code < 31 { char_code } else { 31 };
+        let pos = if pos < 31 { pos } else { 31 };
+
+        (self.bitmap[pos] >> shift) & 1 == 1
+    }
+
+    fn update_bitmap(&mut self, rune: char, pos: usize) {
+        let char_code = support::get_char_code(rune);
+        let shift = if char_code < 31 { char_code } else { 31 };
+        let pos = if pos < 31 { pos } else { 31 };
 
-static mut DICT: Option<Node> = None;
-static mut BITMAP: [u32; 32] = [0; 32];
+        self.bitmap[pos] |= 1 << shift;
+    }
+
+    pub(crate) fn check(&self, word: &str) -> Option<u32> {
+        let mut curr = &self.root;
+
+        for rune in word.chars() {
+            // quick reject
+            if curr.occupied == 0 || !curr.check_bit(rune) {
+                return None;
+            }
+
+            // check which child match the current rune
+            match find_child_pos(&curr.children, rune) {
+                Ok(pos) => curr = &curr.children[pos],
+                Err(_) => return None,
+            }
+        }
+
+        curr.word.as_ref().map(|(_, score)| *score)
+    }
+
+    /// The sum of the scores of all the words.
+    pub(crate) fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// The spelling of the `word` in the dictionary, if it's listed with capitals.
+    pub(crate) fn canonical(&self, word: &str) -> Option<&str> {
+        self.canonical.get(word).map(|canonical| canonical.as_str())
+    }
+
+    /// Walk the trie for the words within `max_edit` Damerau-Levenshtein distance from the
+    /// `word`, and hand each of them to `found` with its score and distance. The walk stops
+    /// early once `found` returns `false`.
+    pub(crate) fn walk<F>(&self, word: &str, max_edit: u8, mut found: F)
+        where F: FnMut(&str, u32, u8) -> bool
+    {
+        let input: Vec<char> = word.chars().collect();
+        let mut walker = Walker {
+            rows: vec![(0..=input.len()).collect()],
+            input,
+            max_edit: max_edit as usize,
+            last_depth: HashMap::new(),
+        };
+
+        walker.descend(&self.root, &mut found);
+    }
+
+    /// Intersect the trie with the `automaton`, and hand each accepted word to `found` with its
+    /// score and distance. The walk stops early once `found` returns `false`.
+    pub(crate) fn intersect<F>(&self, automaton: &mut Automaton, mut found: F)
+        where F: FnMut(&str, u32, u8) -> bool
+    {
+        let start = automaton.start();
+        self.root.intersect(automaton, start, &mut found);
+    }
+
+    /// Walk the paths spelling the `word` with up to `max_edit` of its runes replaced by their
+    /// `alternatives`, and hand each word reached to `found` with its score and the number of
+    /// runes replaced. The walk stops early once `found` returns `false`.
+    pub(crate) fn substitute<'a, A, F>(&self, word: &str, max_edit: u8, alternatives: A, mut found: F)
+        where A: Fn(char) -> &'a [char], F: FnMut(&str, u32, u8) -> bool
+    {
+        let input: Vec<char> = word.chars().collect();
+        if !input.is_empty() {
+            self.root.substitute(&input, max_edit, 0, &alternatives, &mut found);
+        }
+    }
+
+    /// All the words in the dictionary, paired with their scores.
+    pub(crate) fn words(&self) -> Vec<(String, u32)> {
+        let mut words = Vec::new();
+        self.root.collect_words(&mut words);
+        words
+    }
+}
 
 #[derive(Debug)]
 pub(crate) struct Node {
@@ -17,10 +168,6 @@ pub(crate) struct Node {
 }
 
 impl Node {
-    fn new() -> Self {
-        Node::default()
-    }
-
     fn new_with(rune: char, word: Option<(String, u32)>) -> Self {
         Node {
             rune,
@@ -30,99 +177,121 @@ impl Node {
         }
     }
 
-    pub(crate) fn build(rx: Receiver<String>) {
-        if let Some(root) = dict_mut() {
+    /// Insert the word of the `content`, and return the score it replaces, if it was already in.
+    fn insert(&mut self, content: (String, u32), arr: &[char], index: usize) -> Option<u32> {
+        let len = arr.len();
+        if len == 0 || index >= len {
+            eprintln!("Failed to insert: {} ({:?} @ {}), len: {}", content.0, arr, index, len);
+            return None;
+        }
 
-            //TODO: build bit map
+        let rune = arr[index];
+        let pos = match find_child_pos(&self.children, rune) {
+            Ok(pos) => pos,
+            Err(pos) => {
+                self.add_bit(rune);
 
-            for received in rx {
-                let temp: Vec<&str> = received.splitn(2, common::DELIM).collect();
-                if temp[0].is_empty() {
-                    continue;
+                if index == len - 1 {
+                    self.children.insert(pos, Node::new_with(rune, Some(content)));
+                    return None;
                 }
 
-                if let Ok(score) = temp[1].parse::<u32>() {
-                    let word = temp[0].to_owned();
-
-                    let clone = word.clone();
-                    let mut chars = clone.chars();
-                    let mut vec: Vec<char> = Vec::with_capacity(word.len());
-                    let mut pos = 0;
-
-                    while let Some(rune) = chars.next() {
-                        update_bitmap(rune, pos);
-                        vec.push(rune);
-                        pos += 1;
-                    }
-
-                    root.insert((word, score), vec.as_slice(), 0);
-                }
-            }
+                self.children.insert(pos, Node::new_with(rune, None));
+                pos
+            },
+        };
+
+        let child = self.children.get_mut(pos)?;
+        if index == len - 1 {
+            // update child node if this is the whole word
+            child.word.replace(content).map(|(_, score)| score)
+        } else {
+            // insert to the child if not the last character
+            child.insert(content, arr, index + 1)
         }
     }
 
-    pub(crate) fn check(word: &str) -> Option<u32> {
-        if let Some(root) = dict_ref() {
-            let mut curr = root;
-            let mut chars = word.chars();
-
-            while let Some(rune) = chars.next() {
-                // quick reject
-                if curr.occupied == 0 || !curr.check_bit(rune) {
-                    return None;
-                }
+    fn intersect<F>(&self, automaton: &mut Automaton, state: usize, found: &mut F) -> bool
+        where F: FnMut(&str, u32, u8) -> bool
+    {
+        for child in self.children.iter() {
+            let next = match automaton.step(state, child.rune) {
+                Some(next) => next,
+                None => continue,
+            };
 
-                // check which child match the current rune
-                for child in curr.children.iter() {
-                    if child.rune == rune {
-                        curr = child;
-                        break;
+            if let Some((word, score)) = child.word.as_ref() {
+                if let Some(distance) = automaton.distance(next) {
+                    if !found(word, *score, distance) {
+                        return false;
                     }
                 }
             }
 
-            return curr.word.as_ref().and_then(|(_, score)| Some(score.clone()));
+            if !child.intersect(automaton, next, found) {
+                return false;
+            }
         }
 
-        None
+        true
     }
 
-    fn insert(&mut self, content: (String, u32), arr: &[char], index: usize) {
-        let len = arr.len();
-        if len == 0 || index >= len {
-            eprintln!("Failed to insert: {} ({:?} @ {}), len: {}", content.0, arr, index, len);
-            return;
+    fn substitute<'a, A, F>(
+        &self,
+        runes: &[char],
+        edits_left: u8,
+        edits: u8,
+        alternatives: &A,
+        found: &mut F
+    ) -> bool
+        where A: Fn(char) -> &'a [char], F: FnMut(&str, u32, u8) -> bool
+    {
+        let (rune, rest) = match runes.split_first() {
+            Some(split) => split,
+            None => return match self.word.as_ref() {
+                Some((word, score)) => found(word, *score, edits),
+                None => true,
+            },
+        };
+
+        if let Ok(pos) = find_child_pos(&self.children, *rune) {
+            if !self.children[pos].substitute(rest, edits_left, edits, alternatives, found) {
+                return false;
+            }
         }
 
-        let (pos, rune) = find_child_pos(&self.children, arr[index]);
-        if pos == self.children.len() {
-            self.add_bit(rune);
+        if edits_left == 0 {
+            return true;
+        }
 
-            if index == len - 1 {
-                self.children.push(Node::new_with(rune, Some(content)));
-                return;
-            } else {
-                self.children.push(Node::new_with(rune, None));
+        for alternative in alternatives(*rune).iter().filter(|alternative| *alternative != rune) {
+            if let Ok(pos) = find_child_pos(&self.children, *alternative) {
+                let child = &self.children[pos];
+                if !child.substitute(rest, edits_left - 1, edits + 1, alternatives, found) {
+                    return false;
+                }
             }
         }
 
-        if let Some(child) = self.children.get_mut(pos) {
-            if index == len - 1 {
-                // update child node if this is the whole word
-                child.word = Some(content);
-            } else {
-                // insert to the child if not the last character
-                child.insert(content, arr, index + 1);
-            };
+        true
+    }
+
+    fn collect_words(&self, words: &mut Vec<(String, u32)>) {
+        if let Some((word, score)) = self.word.as_ref() {
+            words.push((word.to_owned(), *score));
+        }
+
+        for child in self.children.iter() {
+            child.collect_words(words);
         }
     }
 
     fn check_bit(&self, rune: char) -> bool {
-        (self.occupied >> en_us::get_char_code(rune)) & 1 == 1
+        (self.occupied >> support::get_char_code(rune)) & 1 == 1
     }
 
     fn add_bit(&mut self, rune: char) {
-        self.occupied |= 1 << en_us::get_char_code(rune)
+        self.occupied |= 1 << support::get_char_code(rune)
     }
 }
 
@@ -137,60 +306,97 @@ impl Default for Node {
     }
 }
 
-fn find_child_pos(children: &Vec<Node>, rune: char) -> (usize, char) {
-    let mut index = 0;
+/// The state of a trie walk: the DP rows of the prefixes along the current path against the
+/// input, and the depth where each rune has last been seen on the path, which the transposes
+/// reach back to.
+struct Walker {
+    input: Vec<char>,
+    max_edit: usize,
+    rows: Vec<Vec<usize>>,
+    last_depth: HashMap<char, usize>,
+}
 
-    for child in children.iter() {
-        if child.rune == rune {
-            break;
-        }
+impl Walker {
+    fn descend<F>(&mut self, node: &Node, found: &mut F) -> bool
+        where F: FnMut(&str, u32, u8) -> bool
+    {
+        let depth = self.rows.len();
 
-        index += 1;
-    }
+        for child in node.children.iter() {
+            let row = self.next_row(child.rune);
+            let distance = row[self.input.len()];
 
-    return (index, rune);
-}
+            if let Some((word, score)) = child.word.as_ref() {
+                if distance <= self.max_edit && !found(word, *score, distance as u8) {
+                    return false;
+                }
+            }
 
-fn update_bitmap(rune: char, pos: usize) {
-    let char_code = get_char_code(rune);
-    let shift = if char_code < 31 { char_code } else { 31 };
-    let pos = if pos < 31 { pos } else { 31 };
-    let bitmap = bitmap_mut();
+            // the row minimum never decreases down the path, so nothing below can get closer
+            if child.children.is_empty() || row.iter().all(|dist| *dist > self.max_edit) {
+                continue;
+            }
 
-    bitmap[pos] |= 1 << shift;
-}
+            self.rows.push(row);
+            let prev = self.last_depth.insert(child.rune, depth);
 
-pub(crate) fn check_bitmap(char_code: &u8, pos: usize) -> bool {
-    let shift = if char_code < &31 { *char_code } else { 31 };
-    let bitmap = bitmap_mut();
+            let carry_on = self.descend(child, found);
 
-    (bitmap[pos] >> shift) & 1 == 1
-}
+            match prev {
+                Some(prev) => self.last_depth.insert(child.rune, prev),
+                None => self.last_depth.remove(&child.rune),
+            };
 
-#[inline]
-fn dict_ref() -> Option<&'static Node> {
-    unsafe { DICT.as_ref() }
-}
+            self.rows.pop();
 
-#[inline]
-fn dict_mut() -> Option<&'static mut Node> {
-    unsafe {
-        if DICT.is_none() {
-            DICT.replace(Node::default());
+            if !carry_on {
+                return false;
+            }
         }
 
-        DICT.as_mut()
+        true
     }
-}
 
-#[inline]
-fn bitmap_ref() -> &'static [u32] {
-    unsafe { BITMAP.as_ref() }
-}
+    /// The DP row of the current path extended by the `rune`, following the unrestricted
+    /// Damerau-Levenshtein recurrence, same as `common::damerau_distance`.
+    fn next_row(&self, rune: char) -> Vec<usize> {
+        let depth = self.rows.len();
+        let prev = &self.rows[depth - 1];
+        let mut row = Vec::with_capacity(prev.len());
+        row.push(depth);
+
+        // the last column where the input matches the rune
+        let mut last_col = 0;
+
+        for (j, input) in self.input.iter().enumerate().map(|(idx, input)| (idx + 1, input)) {
+            let cost = if *input == rune { 0 } else { 1 };
+            let mut dist = (prev[j - 1] + cost).min(prev[j] + 1).min(row[j - 1] + 1);
+
+            // transpose the rune with the last match of this input on the path, with the runes
+            // in between inserted and the inputs in between deleted
+            if let Some(last) = self.last_depth.get(input) {
+                if last_col > 0 {
+                    let transposed = self.rows[last - 1][last_col - 1]
+                        + (depth - last - 1) + 1 + (j - last_col - 1);
+
+                    dist = dist.min(transposed);
+                }
+            }
+
+            if cost == 0 {
+                last_col = j;
+            }
+
+            row.push(dist);
+        }
 
-#[inline]
-fn bitmap_mut() -> &'static mut [u32] {
-    unsafe {
-        BITMAP.as_mut()
+        row
     }
-}
\ No newline at end of file
+}
+
+/// The position of the child of the `rune` among the `children`, kept in the order of their
+/// runes, or where it shall be inserted. The scripts with thousands of characters, e.g. Chinese,
+/// branch too widely to scan.
+fn find_child_pos(children: &[Node], rune: char) -> Result<usize, usize> {
+    children.binary_search_by(|child| child.rune.cmp(&rune))
+}